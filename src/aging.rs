@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::databank::{BattingStat, PitchingStat};
+
+
+// Marcel improves every stat 0.6% per year younger than peak and declines it 0.3% per year older.
+const MARCEL_YOUNG: f32 = 0.006;
+const MARCEL_OLD: f32 = -0.003;
+
+
+/// How a stat changes for each year a player is away from peak age. `young` applies per year below
+/// peak and `old` per year above it. A positive slope means more of the stat.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct AgingCurve {
+    pub young: f32,
+    pub old: f32,
+}
+
+/// Aging curve for each stat. Stats without a curve are left alone.
+#[derive(Clone, Debug)]
+pub struct AgingModel {
+    batting: BTreeMap<BattingStat, AgingCurve>,
+    pitching: BTreeMap<PitchingStat, AgingCurve>,
+}
+
+#[derive(Deserialize)]
+struct RawAgingCurve {
+    #[serde(rename = "type")]
+    kind: String,
+    stat: String,
+    young: f32,
+    old: f32,
+}
+
+impl AgingCurve {
+    fn new(young: f32, old: f32) -> Self {
+        AgingCurve { young, old }
+    }
+
+    fn factor(&self, age: f32, peak_age: f32) -> f32 {
+        if age < peak_age {
+            1.0 + self.young * (peak_age - age)
+        }
+        else if age > peak_age {
+            1.0 + self.old * (age - peak_age)
+        }
+        else {
+            1.0
+        }
+    }
+}
+
+impl AgingModel {
+    /// Marcel's aging: every stat, including innings pitched, moves the same way. This makes an
+    /// older hitter strike out less and an older pitcher allow fewer hits.
+    pub fn marcel() -> Self {
+        let curve = AgingCurve::new(MARCEL_YOUNG, MARCEL_OLD);
+        let batting = BattingStat::ALL.iter()
+            .filter(|stat| **stat != BattingStat::Pa && **stat != BattingStat::Ab)
+            .map(|stat| (*stat, curve))
+            .collect();
+        let pitching = PitchingStat::ALL.iter()
+            .map(|stat| (*stat, curve))
+            .collect();
+        AgingModel { batting, pitching }
+    }
+
+    /// Marcel's magnitudes, but stats that are bad for the player get worse with age instead of
+    /// better. Speed stats also fall off faster than the rest.
+    pub fn components() -> Self {
+        let better = AgingCurve::new(MARCEL_YOUNG, MARCEL_OLD);
+        let worse = AgingCurve::new(-MARCEL_YOUNG, -MARCEL_OLD);
+        let speed = AgingCurve::new(MARCEL_YOUNG, 3.0 * MARCEL_OLD);
+        let batting = BattingStat::ALL.iter()
+            .filter_map(|stat| {
+                let curve = match stat {
+                    BattingStat::Pa | BattingStat::Ab => return None,
+                    BattingStat::So | BattingStat::Cs | BattingStat::Gidp => worse,
+                    BattingStat::Sb | BattingStat::Triple => speed,
+                    _ => better,
+                };
+                Some((*stat, curve))
+            })
+            .collect();
+        let pitching = PitchingStat::ALL.iter()
+            .map(|stat| {
+                let curve = match stat {
+                    PitchingStat::Ip | PitchingStat::W | PitchingStat::Sv | PitchingStat::So => better,
+                    _ => worse,
+                };
+                (*stat, curve)
+            })
+            .collect();
+        AgingModel { batting, pitching }
+    }
+
+    /// Load curves from a CSV file with the columns `type,stat,young,old`. The type is either
+    /// batting or pitching and the stat uses the same names as the projection output.
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let mut reader = csv::Reader::from_path(path).map_err(|err| err.to_string())?;
+        let mut batting = BTreeMap::new();
+        let mut pitching = BTreeMap::new();
+        for record in reader.deserialize() {
+            let record: RawAgingCurve = record.map_err(|err| err.to_string())?;
+            let curve = AgingCurve::new(record.young, record.old);
+            match record.kind.to_lowercase().as_str() {
+                "batting" => {
+                    batting.insert(BattingStat::from_str(&record.stat)?, curve);
+                }
+                "pitching" => {
+                    pitching.insert(PitchingStat::from_str(&record.stat)?, curve);
+                }
+                kind => return Err(format!("Unknown aging curve type '{}'", kind)),
+            }
+        }
+        Ok(AgingModel { batting, pitching })
+    }
+
+    /// A built-in preset by name or else a path to a file of curves.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "marcel" => Ok(AgingModel::marcel()),
+            "components" => Ok(AgingModel::components()),
+            path => AgingModel::from_path(Path::new(path)),
+        }
+    }
+
+    pub fn batting_factors(&self, age: f32, peak_age: f32) -> Vec<(BattingStat, f32)> {
+        self.batting.iter()
+            .map(|(stat, curve)| (*stat, curve.factor(age, peak_age)))
+            .collect()
+    }
+
+    pub fn pitching_factors(&self, age: f32, peak_age: f32) -> Vec<(PitchingStat, f32)> {
+        self.pitching.iter()
+            .map(|(stat, curve)| (*stat, curve.factor(age, peak_age)))
+            .collect()
+    }
+
+    pub fn batting_curves(&self) -> impl Iterator<Item = (&BattingStat, &AgingCurve)> {
+        self.batting.iter()
+    }
+
+    pub fn pitching_curves(&self) -> impl Iterator<Item = (&PitchingStat, &AgingCurve)> {
+        self.pitching.iter()
+    }
+}
@@ -0,0 +1,151 @@
+use crate::databank::{BattingStat, PitchingStat};
+use crate::metrics::ErrorMetrics;
+use crate::projection::Capuchin;
+
+
+/// Results of projecting past seasons and scoring them against what actually happened.
+pub struct Backtest {
+    kind: &'static str,
+    unit: &'static str,
+    start_year: u16,
+    end_year: u16,
+    // Year, number of player seasons scored and league PA (or outs) the season before.
+    years: Vec<(u16, usize, u32)>,
+    stats: Vec<StatErrors>,
+}
+
+/// Errors for a single stat. Counts compare the raw projected totals while rates compare per PA
+/// for batters and per IP for pitchers, which removes playing time misses from the picture.
+struct StatErrors {
+    name: &'static str,
+    count: ErrorMetrics,
+    rate: Option<ErrorMetrics>,
+}
+
+impl Backtest {
+    fn new(kind: &'static str, unit: &'static str, start_year: u16, end_year: u16,
+           names: Vec<(&'static str, bool)>) -> Self {
+        let stats = names.into_iter()
+            .map(|(name, has_rate)| StatErrors {
+                name,
+                count: ErrorMetrics::new(),
+                rate: if has_rate { Some(ErrorMetrics::new()) } else { None },
+            })
+            .collect();
+        Backtest {
+            kind,
+            unit,
+            start_year,
+            end_year,
+            years: Vec::new(),
+            stats,
+        }
+    }
+
+    pub fn player_seasons(&self) -> usize {
+        self.years.iter().map(|(_, seasons, _)| seasons).sum()
+    }
+
+    pub fn print(&self) {
+        println!("{} backtest {}-{}: {} player seasons", self.kind, self.start_year, self.end_year,
+                 self.player_seasons());
+        for (year, seasons, league_total) in &self.years {
+            println!("{}\t{} players\t{} league {} in {}", year, seasons, league_total, self.unit,
+                     year.saturating_sub(1));
+        }
+
+        println!("stat\trmse\t\tmae\t\tbias\t\tcorr\t\trate rmse\trate mae\trate bias\trate corr");
+        for stat in &self.stats {
+            let count = &stat.count;
+            print!("{}\t{:8.5}\t{:8.5}\t{:8.5}\t{:8.5}", stat.name, count.rmse(), count.mae(),
+                   count.bias(), count.correlation());
+            if let Some(ref rate) = stat.rate {
+                print!("\t{:8.5}\t{:8.5}\t{:8.5}\t{:8.5}", rate.rmse(), rate.mae(), rate.bias(),
+                       rate.correlation());
+            }
+            println!();
+        }
+    }
+}
+
+/// Project every year from `start_year` to `end_year` and compare each projection with the
+/// player's real season. Players that were projected but did not bat that year are skipped.
+pub fn batting_backtest(capuchin: &mut Capuchin, start_year: u16, end_year: u16) -> Backtest {
+    let names = BattingStat::ALL.iter()
+        .map(|stat| (stat.name(), *stat != BattingStat::Pa))
+        .collect();
+    let mut backtest = Backtest::new("Batting", "PA", start_year, end_year, names);
+
+    for year in start_year..=end_year {
+        let (projections, _) = capuchin.batting_projection(year);
+        let actuals = capuchin.batting_actuals(year);
+        let mut seasons = 0;
+        for projection in &projections {
+            let actual = match actuals.get(projection.playerid()) {
+                Some(actual) if *actual.pa() > 0 => actual,
+                _ => continue,
+            };
+            let projected_pa = projection.stat(BattingStat::Pa);
+            if projected_pa <= 0.0 {
+                continue;
+            }
+            seasons += 1;
+            let actual_pa = actual.stat(BattingStat::Pa);
+            for (stat, errors) in BattingStat::ALL.iter().zip(backtest.stats.iter_mut()) {
+                let projected = projection.stat(*stat);
+                let real = actual.stat(*stat);
+                errors.count.add(projected, real);
+                if let Some(ref mut rate) = errors.rate {
+                    rate.add(projected / projected_pa, real / actual_pa);
+                }
+            }
+        }
+        let league_pa = year.checked_sub(1)
+            .and_then(|last_year| capuchin.batting_league_rates(last_year))
+            .map_or(0, |rates| *rates.pa());
+        backtest.years.push((year, seasons, league_pa));
+    }
+
+    backtest
+}
+
+/// Project every year from `start_year` to `end_year` and compare each projection with the
+/// player's real season. Players that were projected but did not pitch that year are skipped.
+pub fn pitching_backtest(capuchin: &mut Capuchin, start_year: u16, end_year: u16) -> Backtest {
+    let names = PitchingStat::ALL.iter()
+        .map(|stat| (stat.name(), *stat != PitchingStat::Ip))
+        .collect();
+    let mut backtest = Backtest::new("Pitching", "outs", start_year, end_year, names);
+
+    for year in start_year..=end_year {
+        let (projections, _) = capuchin.pitching_projection(year);
+        let actuals = capuchin.pitching_actuals(year);
+        let mut seasons = 0;
+        for projection in &projections {
+            let actual = match actuals.get(projection.playerid()) {
+                Some(actual) if *actual.ipouts() > 0 => actual,
+                _ => continue,
+            };
+            let projected_ip = projection.stat(PitchingStat::Ip);
+            if projected_ip <= 0.0 {
+                continue;
+            }
+            seasons += 1;
+            let actual_ip = actual.stat(PitchingStat::Ip);
+            for (stat, errors) in PitchingStat::ALL.iter().zip(backtest.stats.iter_mut()) {
+                let projected = projection.stat(*stat);
+                let real = actual.stat(*stat);
+                errors.count.add(projected, real);
+                if let Some(ref mut rate) = errors.rate {
+                    rate.add(projected / projected_ip, real / actual_ip);
+                }
+            }
+        }
+        let league_ipouts = year.checked_sub(1)
+            .and_then(|last_year| capuchin.pitching_league_rates(last_year))
+            .map_or(0, |rates| *rates.ipouts());
+        backtest.years.push((year, seasons, league_ipouts));
+    }
+
+    backtest
+}
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::databank::{PitchingSeasonSummaryRates, PitchingStat};
+use crate::era::{self, EraTarget};
+
+
+/// Coefficients of the B factor in Base Runs, which is
+/// `(tb * (h_tb * H + hr_tb * HR) - h * H - hr * HR + bb * BB) * multiplier`.
+#[derive(Clone, Debug, Serialize)]
+pub struct BaseRuns {
+    pub tb: f32,
+    pub h_tb: f32,
+    pub hr_tb: f32,
+    pub h: f32,
+    pub hr: f32,
+    pub bb: f32,
+    pub multiplier: f32,
+}
+
+impl BaseRuns {
+    pub fn new() -> Self {
+        BaseRuns {
+            tb: 1.4,
+            h_tb: 1.12,
+            hr_tb: 4.0,
+            h: 0.6,
+            hr: 3.0,
+            bb: 0.1,
+            multiplier: 1.1,
+        }
+    }
+
+    /// Set coefficients from NAME=VALUE pairs, such as `bb=0.08,multiplier=1.05`. The names are
+    /// tb, h_tb, hr_tb, h, hr, bb and multiplier.
+    pub fn set(&mut self, coefficients: &str) -> Result<(), String> {
+        for pair in coefficients.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let mut parts = pair.splitn(2, '=');
+            let name = parts.next().map(str::trim).unwrap_or("");
+            let value = parts.next()
+                .ok_or_else(|| format!("Expected NAME=VALUE but got '{}'", pair))
+                .and_then(|value| value.trim().parse::<f32>()
+                          .map_err(|err| format!("Unable to parse '{}': {}", pair, err)))?;
+            let coefficient = match name.to_lowercase().as_str() {
+                "tb" => &mut self.tb,
+                "h_tb" => &mut self.h_tb,
+                "hr_tb" => &mut self.hr_tb,
+                "h" => &mut self.h,
+                "hr" => &mut self.hr,
+                "bb" => &mut self.bb,
+                "multiplier" => &mut self.multiplier,
+                _ => return Err(format!("Unknown Base Runs coefficient '{}'", name)),
+            };
+            *coefficient = value;
+        }
+        Ok(())
+    }
+
+    /// Runs allowed by a pitcher with these hits, home runs and walks over `outs`.
+    pub fn runs(&self, h: f32, hr: f32, bb: f32, outs: f32) -> f32 {
+        let a = h + bb - hr;
+        let b = self.unscaled_b(h, hr, bb) * self.multiplier;
+        let c = outs;
+        let d = hr;
+        (a * b) / (b + c) + d
+    }
+
+    /// The same coefficients with the multiplier set so that, season by season, Base Runs gives
+    /// the league's actual earned runs. The multiplier for `year` is estimated from those seasons
+    /// the same way past seasons are moved into a target era. Seasons Base Runs cannot match are
+    /// skipped, as are seasons without any outs, and without any the multiplier is left alone.
+    pub fn calibrated(&self, target: EraTarget, totals: &BTreeMap<u16, PitchingSeasonSummaryRates>,
+                      start_year: u16, year: u16) -> Self {
+        let multipliers: BTreeMap<u16, f32> = totals.range(start_year..year)
+            .filter_map(|(season_year, rates)| {
+                self.season_multiplier(rates).map(|multiplier| (*season_year, multiplier))
+            })
+            .collect();
+        let multiplier = era::target_rate(target, start_year, year,
+                                          |season_year| multipliers.get(&season_year).cloned())
+            .filter(|multiplier| multiplier.is_finite() && *multiplier > 0.0)
+            .unwrap_or(self.multiplier);
+        BaseRuns { multiplier, ..self.clone() }
+    }
+
+    // Solve A * B / (B + C) + D = ER for the multiplier, using the league's rates per out so C is
+    // one.
+    fn season_multiplier(&self, rates: &PitchingSeasonSummaryRates) -> Option<f32> {
+        let rate = |stat| rates.rate(stat).unwrap_or(0.0);
+        let (h, hr, bb) = (rate(PitchingStat::H), rate(PitchingStat::Hr), rate(PitchingStat::Bb));
+        let a = h + bb - hr;
+        let scored = rate(PitchingStat::Er) - hr;
+        let unscaled_b = self.unscaled_b(h, hr, bb);
+        if !a.is_finite() || !scored.is_finite() || !unscaled_b.is_finite() {
+            return None;
+        }
+        if scored <= 0.0 || scored >= a || unscaled_b <= 0.0 {
+            return None;
+        }
+        let b = scored / (a - scored);
+        Some(b / unscaled_b).filter(|multiplier| multiplier.is_finite())
+    }
+
+    fn unscaled_b(&self, h: f32, hr: f32, bb: f32) -> f32 {
+        self.tb * (self.h_tb * h + self.hr_tb * hr) - self.h * h - self.hr * hr + self.bb * bb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::databank::PitchingSeasonSummary;
+    use crate::databank::tests::league_pitching;
+
+    #[test]
+    fn calibrated_skips_empty_seasons() {
+        let base_runs = BaseRuns::new();
+        let totals = BTreeMap::from([
+            (2014, PitchingSeasonSummaryRates::from(league_pitching())),
+            (2015, PitchingSeasonSummaryRates::from(PitchingSeasonSummary::new())),
+        ]);
+        assert_eq!(base_runs.season_multiplier(&totals[&2015]), None);
+        let season = base_runs.season_multiplier(&totals[&2014]).unwrap();
+        for target in &[EraTarget::Latest, EraTarget::Trend] {
+            let calibrated = base_runs.calibrated(*target, &totals, 2014, 2016);
+            assert_eq!(calibrated.multiplier, season);
+        }
+
+        let calibrated = base_runs.calibrated(EraTarget::Latest, &totals, 2015, 2016);
+        assert_eq!(calibrated.multiplier, base_runs.multiplier);
+    }
+
+    #[test]
+    fn calibrated_matches_league_earned_runs() {
+        let totals = BTreeMap::from([(2015, PitchingSeasonSummaryRates::from(league_pitching()))]);
+        let calibrated = BaseRuns::new().calibrated(EraTarget::Latest, &totals, 2015, 2016);
+        let rate = |stat| totals[&2015].rate(stat).unwrap();
+        let runs = calibrated.runs(rate(PitchingStat::H), rate(PitchingStat::Hr),
+                                   rate(PitchingStat::Bb), 1.0);
+        assert!((runs - rate(PitchingStat::Er)).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::metrics::ErrorMetrics;
+
+
+// Columns that never hold stats.
+const SKIP_COLUMNS: &[&str] = &["namefirst", "namelast", "playerid", "lgid", "teamid"];
+
+// Common spellings of stats in other projection systems, mapped to the names Capuchin uses. The
+// divisor converts the other system's units into Capuchin's, e.g. outs into innings.
+const DEFAULT_ALIASES: &[(&str, &str, f64)] = &[
+    ("doubles", "2b", 1.0),
+    ("triples", "3b", 1.0),
+    ("homeruns", "hr", 1.0),
+    ("k", "so", 1.0),
+    ("ipouts", "ip", 3.0),
+];
+
+
+/// Maps the column names of a projection file onto a common set of stat names.
+pub struct Aliases {
+    aliases: HashMap<String, (String, f64)>,
+}
+
+/// A projection file keyed by player with only its numeric columns kept.
+pub struct ProjectionFile {
+    // Column name as it appears in the file, the name it maps to and the divisor for its units.
+    stats: Vec<(String, String, f64)>,
+    players: HashMap<String, Vec<f64>>,
+}
+
+impl Aliases {
+    pub fn new() -> Self {
+        let aliases = DEFAULT_ALIASES.iter()
+            .map(|(alias, stat, divisor)| (alias.to_string(), (stat.to_string(), *divisor)))
+            .collect();
+        Aliases { aliases }
+    }
+
+    /// Add an alias of the form `NAME=STAT` or `NAME=STAT/DIVISOR`, such as `ipouts=ip/3`.
+    pub fn add(&mut self, alias: &str) -> Result<(), String> {
+        let mut parts = alias.splitn(2, '=');
+        let name = parts.next().map(str::trim).unwrap_or("");
+        let target = parts.next().map(str::trim).unwrap_or("");
+        if name.is_empty() || target.is_empty() {
+            return Err(format!("Expected NAME=STAT but got '{}'", alias));
+        }
+
+        let mut target_parts = target.splitn(2, '/');
+        let stat = target_parts.next().map(str::trim).unwrap_or("");
+        let divisor = match target_parts.next() {
+            Some(divisor) => divisor.trim().parse::<f64>()
+                .map_err(|err| format!("Unable to parse divisor in '{}': {}", alias, err))?,
+            None => 1.0,
+        };
+        self.aliases.insert(name.to_lowercase(), (stat.to_lowercase(), divisor));
+        Ok(())
+    }
+
+    fn resolve(&self, column: &str) -> (String, f64) {
+        let column = column.to_lowercase();
+        match self.aliases.get(&column) {
+            Some((stat, divisor)) => (stat.clone(), *divisor),
+            None => (column, 1.0),
+        }
+    }
+}
+
+impl ProjectionFile {
+    /// Load a projection, using the column named `id_column` (case insensitive) as the player ID.
+    /// Columns that hold anything other than numbers are dropped.
+    pub fn load(path: &Path, id_column: &str, aliases: &Aliases) -> Result<Self, csv::Error> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let headers = reader.headers()?.clone();
+        let id_idx = headers.iter()
+            .position(|header| header.eq_ignore_ascii_case(id_column));
+        let id_idx = match id_idx {
+            Some(idx) => idx,
+            None => {
+                let message = format!("No '{}' column in {}", id_column, path.display());
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message).into());
+            }
+        };
+
+        let mut records = Vec::new();
+        for record in reader.records() {
+            records.push(record?);
+        }
+
+        let mut columns = Vec::new();
+        for (idx, header) in headers.iter().enumerate() {
+            if idx == id_idx || SKIP_COLUMNS.contains(&header.to_lowercase().as_str()) {
+                continue;
+            }
+            // Numeric when every value is a number or empty, and at least one is a number.
+            let mut parsed = false;
+            let numeric = records.iter()
+                .filter_map(|record| record.get(idx).map(str::trim))
+                .filter(|value| !value.is_empty())
+                .all(|value| {
+                    parsed = true;
+                    value.parse::<f64>().is_ok()
+                });
+            if numeric && parsed {
+                columns.push(idx);
+            }
+        }
+
+        let stats = columns.iter()
+            .map(|idx| {
+                let header = &headers[*idx];
+                let (stat, divisor) = aliases.resolve(header);
+                (header.to_string(), stat, divisor)
+            })
+            .collect();
+
+        let mut players = HashMap::new();
+        for record in &records {
+            let playerid = match record.get(id_idx) {
+                Some(playerid) => playerid.to_string(),
+                None => continue,
+            };
+            let values = columns.iter()
+                .map(|idx| record.get(*idx)
+                     .and_then(|value| value.trim().parse::<f64>().ok())
+                     .unwrap_or(f64::NAN))
+                .collect();
+            players.insert(playerid, values);
+        }
+
+        Ok(ProjectionFile { stats, players })
+    }
+
+    fn stat_idx(&self, stat: &str) -> Option<usize> {
+        self.stats.iter().position(|(_, name, _)| name == stat)
+    }
+
+    fn value(&self, values: &[f64], idx: usize) -> f64 {
+        values[idx] / self.stats[idx].2
+    }
+
+    fn has_nan(&self, playerid: &str) -> bool {
+        self.players.get(playerid)
+            .is_some_and(|values| values.iter().any(|value| value.is_nan()))
+    }
+}
+
+/// Compare two projections the way marcel-compare.py does: report the players missing from
+/// either side, players with NaNs and age disagreements, then the error of every shared stat.
+/// Differences are taken as `reference - other`.
+pub fn compare(reference: &ProjectionFile, other: &ProjectionFile) {
+    let reference_players: BTreeSet<&String> = reference.players.keys().collect();
+    let other_players: BTreeSet<&String> = other.players.keys().collect();
+
+    let only_reference: Vec<&str> = reference_players.difference(&other_players)
+        .map(|playerid| playerid.as_str())
+        .collect();
+    if !only_reference.is_empty() {
+        println!("Player only in reference: {}", only_reference.join(", "));
+    }
+    let only_other: Vec<&str> = other_players.difference(&reference_players)
+        .map(|playerid| playerid.as_str())
+        .collect();
+    if !only_other.is_empty() {
+        println!("Player only in other: {}", only_other.join(", "));
+    }
+
+    let mut nan_players = Vec::new();
+    let mut mutual_players = Vec::new();
+    for playerid in reference_players.intersection(&other_players) {
+        if reference.has_nan(playerid) || other.has_nan(playerid) {
+            nan_players.push(playerid.as_str());
+        }
+        else {
+            mutual_players.push(*playerid);
+        }
+    }
+    if !nan_players.is_empty() {
+        println!("Player with NaNs: {}", nan_players.join(", "));
+    }
+
+    // Only compare stats found in both files, in the order of the reference file.
+    let shared_stats: Vec<(&str, usize, usize)> = reference.stats.iter()
+        .enumerate()
+        .filter_map(|(ref_idx, (header, stat, _))| {
+            other.stat_idx(stat).map(|other_idx| (header.as_str(), ref_idx, other_idx))
+        })
+        .collect();
+
+    let mut differences: Vec<ErrorMetrics> = shared_stats.iter()
+        .map(|_| ErrorMetrics::new())
+        .collect();
+    for playerid in mutual_players {
+        let ref_values = &reference.players[playerid];
+        let other_values = &other.players[playerid];
+        for ((header, ref_idx, other_idx), errors) in shared_stats.iter().zip(differences.iter_mut()) {
+            let ref_stat = reference.value(ref_values, *ref_idx);
+            let other_stat = other.value(other_values, *other_idx);
+            if header.eq_ignore_ascii_case("age") && (ref_stat - other_stat).abs() > 0.5 {
+                println!("{}'s age doesn't agree: {} != {}", playerid, ref_stat, other_stat);
+            }
+            errors.add(ref_stat as f32, other_stat as f32);
+        }
+    }
+
+    println!("stat\trmse\t\tstddev\t\tvariance");
+    for ((header, _, _), errors) in shared_stats.iter().zip(differences.iter()) {
+        println!("{}\t{:>8}\t{:>8}\t{:>8}", header, significant(errors.rmse(), 5),
+                 significant(errors.stddev(), 5), significant(errors.variance(), 5));
+    }
+}
+
+// Format a value to `digits` significant digits the way Python's `{:8.5}` does: like `%g`, but a
+// fixed point value keeps at least one digit after the point and scientific notation starts at an
+// exponent of `digits - 1`.
+fn significant(value: f64, digits: usize) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    if value == 0.0 {
+        return if value.is_sign_negative() { "-0.0" } else { "0.0" }.to_string();
+    }
+
+    // Round to the digits first, since rounding can carry into the next power of ten.
+    let scientific = format!("{:.*e}", digits - 1, value);
+    let (mantissa, exponent) = scientific.split_once('e').expect("Scientific notation has an exponent");
+    let exponent: i32 = exponent.parse().expect("Exponent is a number");
+    if exponent < -4 || exponent >= digits as i32 - 1 {
+        let mantissa = if mantissa.contains('.') {
+            mantissa.trim_end_matches('0').trim_end_matches('.')
+        }
+        else {
+            mantissa
+        };
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    }
+    else {
+        let decimals = (digits as i32 - 1 - exponent) as usize;
+        let fixed = format!("{:.*}", decimals, value);
+        let fixed = fixed.trim_end_matches('0');
+        if fixed.ends_with('.') {
+            format!("{}0", fixed)
+        }
+        else {
+            fixed.to_string()
+        }
+    }
+}
@@ -2,29 +2,129 @@ use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::path::Path;
+use std::str::FromStr;
 
-use csv;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
+use crate::baseruns::BaseRuns;
+use crate::fip::{FipConstants, FIP_BB, FIP_HR, FIP_SO};
+use crate::linearweights::LinearWeights;
+use crate::minors;
+use crate::output::{self, Metadata, OutputFormat};
+use crate::park::ParkFactor;
+
+
+// About how many batters a pitcher faces for every out recorded. Used to put a two-way player's
+// innings on the same scale as their PA.
+const BATTERS_FACED_PER_OUT: f32 = 1.4;
 
 pub struct Players {
     players: HashMap<String, Player>,
     batting: Vec<BattingSeason>,
     pitching: Vec<PitchingSeason>,
+    // Minor league seasons already translated to their MLB equivalents.
+    minor_batting: Vec<BattingSeason>,
+    minor_pitching: Vec<PitchingSeason>,
+    // A season with at least this many PA and IP counts as both a batter and a pitcher.
+    two_way_pa: u16,
+    two_way_ip: u16,
+    // Earliest season loaded.
+    first_year: u16,
 }
 
 pub struct Player {
     ipouts: BTreeMap<u16, u16>,
     pa: BTreeMap<u16, u16>,
+    stints: BTreeMap<u16, Vec<Stint>>,
+}
+
+/// A player's time with one team during a season.
+#[derive(Debug, PartialEq)]
+pub struct Stint {
+    stint: u8,
+    teamid: String,
+    lgid: String,
 }
 
+#[derive(PartialEq)]
 enum PlayerType {
     NoAppearances,
     Batter,
     Pitcher,
+    TwoWay,
     Unknown,
 }
 
+/// Batting components that can be compared between a projection and a real season.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BattingStat {
+    Pa,
+    Ab,
+    R,
+    H,
+    Double,
+    Triple,
+    Hr,
+    Rbi,
+    Sb,
+    Cs,
+    Bb,
+    So,
+    Ibb,
+    Hbp,
+    Sh,
+    Sf,
+    Gidp,
+}
+
+/// Rate stats derived from a batting projection's components.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BattingRate {
+    Avg,
+    Obp,
+    Slg,
+    Ops,
+    Iso,
+    Babip,
+    KPct,
+    BbPct,
+    Woba,
+    Wraa,
+    WrcPlus,
+}
+
+/// Rate stats derived from a pitching projection's components.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PitchingRate {
+    Fip,
+    Xfip,
+    Whip,
+    K9,
+    Bb9,
+    Hr9,
+    KPct,
+    BbPct,
+}
+
+/// Pitching components that can be compared between a projection and a real season.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PitchingStat {
+    Ip,
+    W,
+    L,
+    Sv,
+    H,
+    R,
+    Er,
+    Hr,
+    So,
+    Bb,
+    Ibb,
+    Hbp,
+    Wp,
+    Bk,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 struct RawBattingSeason {
@@ -32,14 +132,12 @@ struct RawBattingSeason {
     playerid: String,
     #[serde(rename = "yearID")]
     yearid: u16,
-    /*
     #[serde(rename = "stint")]
-    stint: String,
+    stint: u8,
     #[serde(rename = "teamID")]
     teamid: String,
     #[serde(rename = "lgID")]
     lgid: String,
-    */
     g: u8,
     ab: u16,
     r: u8,
@@ -61,10 +159,13 @@ struct RawBattingSeason {
     gidp: Option<u8>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct BattingSeason {
     playerid: String,
     yearid: u16,
+    stint: u8,
+    teamid: String,
+    lgid: String,
     g: u8,
     pa: u16,
     ab: u16,
@@ -92,14 +193,12 @@ struct RawPitchingSeason {
     playerid: String,
     #[serde(rename = "yearID")]
     yearid: u16,
-    /*
     #[serde(rename = "stint")]
-    stint: String,
+    stint: u8,
     #[serde(rename = "teamID")]
     teamid: String,
     #[serde(rename = "lgID")]
     lgid: String,
-    */
     w: u8,
     l: u8,
     g: u8,
@@ -135,10 +234,13 @@ struct RawPitchingSeason {
     */
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct PitchingSeason {
     playerid: String,
     yearid: u16,
+    stint: u8,
+    teamid: String,
+    lgid: String,
     ipouts: u16,
     w: u8,
     l: u8,
@@ -237,12 +339,29 @@ pub struct PitchingSeasonSummaryRates {
     bk: f32,
 }
 
+/// Names, handedness and other IDs of a player, written next to their projection.
+#[derive(Debug, Default)]
+pub struct PlayerInfo {
+    pub name_first: String,
+    pub name_last: String,
+    pub bats: String,
+    pub throws: String,
+    pub key_mlbam: String,
+    pub key_retro: String,
+    pub key_fangraphs: String,
+    pub debut: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct IntBattingProjection {
     playerid: String,
-    age: u8,
+    age: f32,
+    age_convention: &'static str,
     year: u16,
     reliability: f32,
+    // Weighted PA behind the regressed rates, the player's own plus the regression amount.
+    #[serde(skip)]
+    sample: f32,
     pa: f32,
     ab: f32,
     r: f32,
@@ -267,9 +386,13 @@ pub struct IntBattingProjection {
 #[derive(Debug, Serialize)]
 pub struct IntPitchingProjection {
     playerid: String,
-    age: u8,
+    age: f32,
+    age_convention: &'static str,
     year: u16,
     reliability: f32,
+    // Weighted outs behind the regressed rates, the player's own plus the regression amount.
+    #[serde(skip)]
+    sample: f32,
     ipouts: f32,
     w: f32,
     l: f32,
@@ -289,9 +412,35 @@ pub struct IntPitchingProjection {
 #[derive(Debug, Serialize)]
 pub struct BattingProjection {
     playerid: String,
-    age: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_first: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_last: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bats: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throws: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_mlbam: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_retro: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_fangraphs: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debut: Option<String>,
+    teamid: String,
+    lgid: String,
+    // Only written when two-way players are classified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    two_way: Option<bool>,
+    #[serde(serialize_with = "serialize_age")]
+    age: f32,
+    age_convention: &'static str,
     year: u16,
     reliability: f32,
+    // Weighted PA behind the regressed rates, the player's own plus the regression amount.
+    #[serde(skip)]
+    sample: f32,
     pa: f32,
     ab: f32,
     r: f32,
@@ -311,14 +460,62 @@ pub struct BattingProjection {
     sh: f32,
     sf: f32,
     gidp: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    avg: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    obp: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slg: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ops: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iso: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    babip: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    k_pct: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bb_pct: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    woba: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wraa: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wrc_plus: Option<f32>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct PitchingProjection {
     playerid: String,
-    age: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_first: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_last: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bats: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throws: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_mlbam: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_retro: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_fangraphs: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debut: Option<String>,
+    teamid: String,
+    lgid: String,
+    // Only written when two-way players are classified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    two_way: Option<bool>,
+    #[serde(serialize_with = "serialize_age")]
+    age: f32,
+    age_convention: &'static str,
     year: u16,
     reliability: f32,
+    // Weighted outs behind the regressed rates, the player's own plus the regression amount.
+    #[serde(skip)]
+    sample: f32,
     ip: f32,
     w: f32,
     l: f32,
@@ -328,6 +525,9 @@ pub struct PitchingProjection {
     er: f32,
     bsrer: f32,
     era: f32,
+    // Projected earned runs and Base Runs before rounding or blending into ERA.
+    raw_er: f32,
+    raw_bsrer: f32,
     hr: f32,
     so: f32,
     bb: f32,
@@ -335,6 +535,172 @@ pub struct PitchingProjection {
     hbp: f32,
     wp: f32,
     bk: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fip: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xfip: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    whip: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    k_9: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bb_9: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hr_9: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    k_pct: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bb_pct: Option<f32>,
+}
+
+impl BattingStat {
+    pub const ALL: [BattingStat; 17] = [
+        BattingStat::Pa, BattingStat::Ab, BattingStat::R, BattingStat::H, BattingStat::Double,
+        BattingStat::Triple, BattingStat::Hr, BattingStat::Rbi, BattingStat::Sb, BattingStat::Cs,
+        BattingStat::Bb, BattingStat::So, BattingStat::Ibb, BattingStat::Hbp, BattingStat::Sh,
+        BattingStat::Sf, BattingStat::Gidp,
+    ];
+
+    /// Name of the stat, matching the column header in the projection output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BattingStat::Pa => "pa",
+            BattingStat::Ab => "ab",
+            BattingStat::R => "r",
+            BattingStat::H => "h",
+            BattingStat::Double => "2B",
+            BattingStat::Triple => "3B",
+            BattingStat::Hr => "hr",
+            BattingStat::Rbi => "rbi",
+            BattingStat::Sb => "sb",
+            BattingStat::Cs => "cs",
+            BattingStat::Bb => "bb",
+            BattingStat::So => "so",
+            BattingStat::Ibb => "ibb",
+            BattingStat::Hbp => "hbp",
+            BattingStat::Sh => "sh",
+            BattingStat::Sf => "sf",
+            BattingStat::Gidp => "gidp",
+        }
+    }
+}
+
+impl FromStr for BattingStat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        BattingStat::ALL.iter()
+            .find(|stat| stat.name().eq_ignore_ascii_case(name.trim()))
+            .copied()
+            .ok_or_else(|| format!("Unknown batting stat '{}'", name))
+    }
+}
+
+impl PitchingStat {
+    pub const ALL: [PitchingStat; 14] = [
+        PitchingStat::Ip, PitchingStat::W, PitchingStat::L, PitchingStat::Sv, PitchingStat::H,
+        PitchingStat::R, PitchingStat::Er, PitchingStat::Hr, PitchingStat::So, PitchingStat::Bb,
+        PitchingStat::Ibb, PitchingStat::Hbp, PitchingStat::Wp, PitchingStat::Bk,
+    ];
+
+    /// Name of the stat, matching the column header in the projection output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PitchingStat::Ip => "ip",
+            PitchingStat::W => "w",
+            PitchingStat::L => "l",
+            PitchingStat::Sv => "sv",
+            PitchingStat::H => "h",
+            PitchingStat::R => "r",
+            PitchingStat::Er => "er",
+            PitchingStat::Hr => "hr",
+            PitchingStat::So => "so",
+            PitchingStat::Bb => "bb",
+            PitchingStat::Ibb => "ibb",
+            PitchingStat::Hbp => "hbp",
+            PitchingStat::Wp => "wp",
+            PitchingStat::Bk => "bk",
+        }
+    }
+}
+
+impl BattingRate {
+    pub const ALL: [BattingRate; 11] = [
+        BattingRate::Avg, BattingRate::Obp, BattingRate::Slg, BattingRate::Ops, BattingRate::Iso,
+        BattingRate::Babip, BattingRate::KPct, BattingRate::BbPct, BattingRate::Woba,
+        BattingRate::Wraa, BattingRate::WrcPlus,
+    ];
+
+    /// Name of the rate, matching the column header in the projection output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BattingRate::Avg => "avg",
+            BattingRate::Obp => "obp",
+            BattingRate::Slg => "slg",
+            BattingRate::Ops => "ops",
+            BattingRate::Iso => "iso",
+            BattingRate::Babip => "babip",
+            BattingRate::KPct => "k_pct",
+            BattingRate::BbPct => "bb_pct",
+            BattingRate::Woba => "woba",
+            BattingRate::Wraa => "wraa",
+            BattingRate::WrcPlus => "wrc_plus",
+        }
+    }
+}
+
+impl FromStr for BattingRate {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        BattingRate::ALL.iter()
+            .find(|rate| rate.name().eq_ignore_ascii_case(name.trim()))
+            .copied()
+            .ok_or_else(|| format!("Unknown batting rate '{}'", name))
+    }
+}
+
+impl PitchingRate {
+    pub const ALL: [PitchingRate; 8] = [
+        PitchingRate::Fip, PitchingRate::Xfip, PitchingRate::Whip, PitchingRate::K9,
+        PitchingRate::Bb9, PitchingRate::Hr9, PitchingRate::KPct, PitchingRate::BbPct,
+    ];
+
+    /// Name of the rate, matching the column header in the projection output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PitchingRate::Fip => "fip",
+            PitchingRate::Xfip => "xfip",
+            PitchingRate::Whip => "whip",
+            PitchingRate::K9 => "k_9",
+            PitchingRate::Bb9 => "bb_9",
+            PitchingRate::Hr9 => "hr_9",
+            PitchingRate::KPct => "k_pct",
+            PitchingRate::BbPct => "bb_pct",
+        }
+    }
+}
+
+impl FromStr for PitchingRate {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        PitchingRate::ALL.iter()
+            .find(|rate| rate.name().eq_ignore_ascii_case(name.trim()))
+            .copied()
+            .ok_or_else(|| format!("Unknown pitching rate '{}'", name))
+    }
+}
+
+impl FromStr for PitchingStat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        PitchingStat::ALL.iter()
+            .find(|stat| stat.name().eq_ignore_ascii_case(name.trim()))
+            .copied()
+            .ok_or_else(|| format!("Unknown pitching stat '{}'", name))
+    }
 }
 
 impl Players {
@@ -343,9 +709,21 @@ impl Players {
             players: HashMap::new(),
             batting: Vec::new(),
             pitching: Vec::new(),
+            minor_batting: Vec::new(),
+            minor_pitching: Vec::new(),
+            two_way_pa: u16::MAX,
+            two_way_ip: u16::MAX,
+            first_year: u16::MAX,
         }
     }
 
+    /// Treat seasons with at least `pa` PA and `ip` IP as two-way seasons. These players keep
+    /// both their batting and pitching.
+    pub fn set_two_way_thresholds(&mut self, pa: u16, ip: u16) {
+        self.two_way_pa = pa;
+        self.two_way_ip = ip;
+    }
+
     pub fn load_batting(&mut self, batting_csv: &Path) -> Result<(), csv::Error> {
         let mut rdr = csv::Reader::from_path(batting_csv)?;
         for record in rdr.deserialize() {
@@ -354,6 +732,7 @@ impl Players {
             let player = self.players.entry(record.playerid.clone())
                 .or_insert(Player::new());
             player.add_pa(&record);
+            self.first_year = self.first_year.min(record.yearid);
             self.batting.push(record);
         }
 
@@ -366,6 +745,17 @@ impl Players {
             .collect()
     }
 
+    /// Combine each player's stints in a year into a single season summary.
+    pub fn batting_summaries(&self, year: u16) -> HashMap<&String, BattingSeasonSummary> {
+        let mut summaries = HashMap::new();
+        for season in self.batting.iter().filter(|season| season.yearid == year) {
+            summaries.entry(&season.playerid)
+                .or_insert_with(BattingSeasonSummary::new)
+                .mut_add_season(season);
+        }
+        summaries
+    }
+
     pub fn load_pitching(&mut self, pitching_csv: &Path) -> Result<(), csv::Error> {
         let mut rdr = csv::Reader::from_path(pitching_csv)?;
         for record in rdr.deserialize() {
@@ -374,6 +764,7 @@ impl Players {
             let player = self.players.entry(record.playerid.clone())
                 .or_insert(Player::new());
             player.add_ip(&record);
+            self.first_year = self.first_year.min(record.yearid);
             self.pitching.push(record);
         }
 
@@ -386,29 +777,149 @@ impl Players {
             .collect()
     }
 
+    /// Combine each player's stints in a year into a single season summary.
+    pub fn pitching_summaries(&self, year: u16) -> HashMap<&String, PitchingSeasonSummary> {
+        let mut summaries = HashMap::new();
+        for season in self.pitching.iter().filter(|season| season.yearid == year) {
+            summaries.entry(&season.playerid)
+                .or_insert_with(PitchingSeasonSummary::new)
+                .mut_add_season(season);
+        }
+        summaries
+    }
+
+    /// Load minor league batting with the same columns as Batting.csv plus a level column. Each
+    /// season is translated to its MLB equivalent using the factor for its level.
+    pub fn load_minor_batting(&mut self, batting_csv: &Path, translations: &minors::Translations)
+        -> Result<(), csv::Error>
+    {
+        let mut rdr = csv::Reader::from_path(batting_csv)?;
+        let headers = rdr.headers()?.clone();
+        let level_idx = level_column(&headers)?;
+        for record in rdr.records() {
+            let record = record?;
+            let level = record.get(level_idx).unwrap_or("");
+            let factor = translations.factor(level)
+                .ok_or_else(|| invalid_data(format!("No translation factor for level '{}'", level)))?;
+            let season: RawBattingSeason = record.deserialize(Some(&headers))?;
+            let mut season = BattingSeason::from(season);
+            season.translate(factor);
+            self.minor_batting.push(season);
+        }
+
+        Ok(())
+    }
+
+    pub fn minor_batting_seasons(&self, start_year: u16, end_year: u16) -> Vec<&BattingSeason> {
+        self.minor_batting.iter().filter(|season| start_year <= season.yearid &&
+                                                  season.yearid <= end_year)
+            .collect()
+    }
+
+    /// Load minor league pitching with the same columns as Pitching.csv plus a level column. Each
+    /// season is translated to its MLB equivalent using the factor for its level.
+    pub fn load_minor_pitching(&mut self, pitching_csv: &Path, translations: &minors::Translations)
+        -> Result<(), csv::Error>
+    {
+        let mut rdr = csv::Reader::from_path(pitching_csv)?;
+        let headers = rdr.headers()?.clone();
+        let level_idx = level_column(&headers)?;
+        for record in rdr.records() {
+            let record = record?;
+            let level = record.get(level_idx).unwrap_or("");
+            let factor = translations.factor(level)
+                .ok_or_else(|| invalid_data(format!("No translation factor for level '{}'", level)))?;
+            let season: RawPitchingSeason = record.deserialize(Some(&headers))?;
+            let mut season = PitchingSeason::from(season);
+            season.translate(factor);
+            self.minor_pitching.push(season);
+        }
+
+        Ok(())
+    }
+
+    pub fn minor_pitching_seasons(&self, start_year: u16, end_year: u16) -> Vec<&PitchingSeason> {
+        self.minor_pitching.iter().filter(|season| start_year <= season.yearid &&
+                                                   season.yearid <= end_year)
+            .collect()
+    }
+
+    /// Earliest season in the batting or pitching data.
+    pub fn first_year(&self) -> u16 {
+        self.first_year
+    }
+
+    /// PA in a year across all stints.
+    pub fn season_pa(&self, playerid: &str, year: u16) -> u16 {
+        self.players.get(playerid)
+            .and_then(|player| player.pa.get(&year))
+            .map_or(0, |pa| *pa)
+    }
+
+    /// Outs recorded in a year across all stints.
+    pub fn season_ipouts(&self, playerid: &str, year: u16) -> u16 {
+        self.players.get(playerid)
+            .and_then(|player| player.ipouts.get(&year))
+            .map_or(0, |ipouts| *ipouts)
+    }
+
+    /// Every team a player played for in a year, in stint order.
+    pub fn stints(&self, playerid: &str, year: u16) -> &[Stint] {
+        self.players.get(playerid)
+            .and_then(|player| player.stints.get(&year))
+            .map_or(&[], |stints| stints.as_slice())
+    }
+
     /// Remove players playing out of position. This counts batters pitching and pitchers batting.
     pub fn remove_out_of_position_players(&mut self) {
         let keep: Vec<bool> = self.batting.iter()
-            .map(|season| self.player_is_batter(&season))
+            .map(|season| self.player_is_batter(season))
             .collect();
         let mut iter = keep.iter();
         self.batting.retain(|_| *iter.next().unwrap());
 
         let keep: Vec<bool> = self.pitching.iter()
-            .map(|season| self.player_is_pitcher(&season))
+            .map(|season| self.player_is_pitcher(season))
             .collect();
         let mut iter = keep.iter();
         self.pitching.retain(|_| *iter.next().unwrap());
     }
 
-    fn player_is_batter(&self, season: &BattingSeason) -> bool {
+    /// Whether the season belongs to a player who mostly batted that year.
+    pub fn player_is_batter(&self, season: &BattingSeason) -> bool {
         self.players.get(&season.playerid)
-            .map_or(true, |player| player.is_batter(season.yearid))
+            .is_none_or(|player| player.is_batter(season.yearid, self.two_way_pa,
+                                                  self.two_way_ip))
     }
 
-    fn player_is_pitcher(&self, season: &PitchingSeason) -> bool {
+    /// Whether the season belongs to a player who mostly pitched that year.
+    pub fn player_is_pitcher(&self, season: &PitchingSeason) -> bool {
         self.players.get(&season.playerid)
-            .map_or(true, |player| player.is_pitcher(season.yearid))
+            .is_none_or(|player| player.is_pitcher(season.yearid, self.two_way_pa,
+                                                   self.two_way_ip))
+    }
+
+    /// For players with a two-way season between the years, the share of their playing time
+    /// spent batting. Innings are turned into batters faced to compare with PA.
+    pub fn two_way_batting_share(&self, playerid: &str, start_year: u16, end_year: u16)
+        -> Option<f32>
+    {
+        let player = self.players.get(playerid)?;
+        let two_way = (start_year..=end_year)
+            .any(|year| player.player_type(year, self.two_way_pa, self.two_way_ip)
+                        == PlayerType::TwoWay);
+        if !two_way {
+            return None;
+        }
+
+        let pa: u32 = player.pa.range(start_year..=end_year)
+            .map(|(_, pa)| *pa as u32)
+            .sum();
+        let ipouts: u32 = player.ipouts.range(start_year..=end_year)
+            .map(|(_, ipouts)| *ipouts as u32)
+            .sum();
+        let batters_faced = ipouts as f32 * BATTERS_FACED_PER_OUT;
+        Some(pa as f32 / (pa as f32 + batters_faced))
     }
 }
 
@@ -417,6 +928,16 @@ impl Player {
         Player {
             ipouts: BTreeMap::new(),
             pa: BTreeMap::new(),
+            stints: BTreeMap::new(),
+        }
+    }
+
+    // Batting and pitching both list the same stints so only keep the first of each.
+    fn add_stint(&mut self, year: u16, stint: u8, teamid: &str, lgid: &str) {
+        let stints = self.stints.entry(year).or_default();
+        if stints.iter().all(|existing| existing.stint != stint) {
+            stints.push(Stint::new(stint, teamid, lgid));
+            stints.sort_by_key(|stint| stint.stint);
         }
     }
 
@@ -425,6 +946,7 @@ impl Player {
         let ipouts = record.ipouts;
         let season_ipouts = self.ipouts.entry(year).or_insert(0);
         *season_ipouts += ipouts;
+        self.add_stint(year, record.stint, &record.teamid, &record.lgid);
     }
 
     fn add_pa(&mut self, record: &BattingSeason) {
@@ -432,9 +954,10 @@ impl Player {
         let pa = record.pa;
         let season_pa = self.pa.entry(year).or_insert(0);
         *season_pa += pa;
+        self.add_stint(year, record.stint, &record.teamid, &record.lgid);
     }
 
-    fn player_type(&self, year: u16) -> PlayerType {
+    fn player_type(&self, year: u16, two_way_pa: u16, two_way_ip: u16) -> PlayerType {
         let year_pa = self.pa.get(&year);
         // Convert outs recorded into IP. If only one or two outs were recorded that season, still
         // map that to one IP so it compares better with the PA.
@@ -446,7 +969,10 @@ impl Player {
 
         match (year_pa, year_ip) {
             (Some(pa), Some(ip)) => {
-                if *pa > ip {
+                if *pa >= two_way_pa && ip >= two_way_ip {
+                    PlayerType::TwoWay
+                }
+                else if *pa > ip {
                     PlayerType::Batter
                 }
                 else if *pa < ip {
@@ -457,25 +983,27 @@ impl Player {
                 }
             },
             (Some(_), None) => PlayerType::Batter,
-            (None, Some(_)) => PlayerType::Batter,
+            (None, Some(_)) => PlayerType::Pitcher,
             (None, None) => PlayerType::NoAppearances,
         }
     }
 
-    fn is_batter(&self, year: u16) -> bool {
-        match self.player_type(year) {
+    fn is_batter(&self, year: u16, two_way_pa: u16, two_way_ip: u16) -> bool {
+        match self.player_type(year, two_way_pa, two_way_ip) {
             PlayerType::NoAppearances => false,
             PlayerType::Batter => true,
             PlayerType::Pitcher => false,
+            PlayerType::TwoWay => true,
             PlayerType::Unknown => true,
         }
     }
 
-    fn is_pitcher(&self, year: u16) -> bool {
-        match self.player_type(year) {
+    fn is_pitcher(&self, year: u16, two_way_pa: u16, two_way_ip: u16) -> bool {
+        match self.player_type(year, two_way_pa, two_way_ip) {
             PlayerType::NoAppearances => false,
             PlayerType::Batter => false,
             PlayerType::Pitcher => true,
+            PlayerType::TwoWay => true,
             PlayerType::Unknown => true,
         }
     }
@@ -486,6 +1014,9 @@ impl From<RawBattingSeason> for BattingSeason {
         BattingSeason {
             playerid: csv.playerid,
             yearid: csv.yearid,
+            stint: csv.stint,
+            teamid: csv.teamid,
+            lgid: csv.lgid,
             g: csv.g,
             pa: csv.ab + csv.bb + csv.hbp.unwrap_or(0) as u16 +
                 csv.sf.unwrap_or(0) as u16 + csv.sh.unwrap_or(0) as u16,
@@ -514,6 +1045,9 @@ impl From<RawPitchingSeason> for PitchingSeason {
         PitchingSeason {
             playerid: csv.playerid,
             yearid: csv.yearid,
+            stint: csv.stint,
+            teamid: csv.teamid,
+            lgid: csv.lgid,
             w: csv.w,
             l: csv.l,
             g: csv.g,
@@ -534,7 +1068,85 @@ impl From<RawPitchingSeason> for PitchingSeason {
     }
 }
 
+impl Stint {
+    pub fn new(stint: u8, teamid: &str, lgid: &str) -> Self {
+        Stint {
+            stint,
+            teamid: teamid.to_string(),
+            lgid: lgid.to_string(),
+        }
+    }
+
+    pub fn teamid(&self) -> &str {
+        &self.teamid
+    }
+
+    pub fn lgid(&self) -> &str {
+        &self.lgid
+    }
+}
+
 impl BattingSeason {
+    /// The season as if it were played in a neutral park.
+    pub fn neutralized(&self, park: &ParkFactor) -> Self {
+        let scale_u8 = |stat: u8, factor: f32| (stat as f32 / factor).round() as u8;
+        let scale_u16 = |stat: u16, factor: f32| (stat as f32 / factor).round() as u16;
+        let mut season = self.clone();
+        let singles = self.h - (self.double as u16 + self.triple as u16 + self.hr as u16);
+        season.r = scale_u8(self.r, park.r);
+        season.rbi = scale_u8(self.rbi, park.r);
+        season.double = scale_u8(self.double, park.double);
+        season.triple = scale_u8(self.triple, park.triple);
+        season.hr = scale_u8(self.hr, park.hr);
+        season.h = scale_u16(singles, park.h)
+            + season.double as u16 + season.triple as u16 + season.hr as u16;
+        season.so = scale_u16(self.so, park.so);
+        season.bb = scale_u16(self.bb, park.bb);
+        season.ibb = scale_u8(self.ibb, park.bb);
+        season.fit_to_pa();
+        season
+    }
+
+    /// Scale the stats a batter wants more of by `factor` and strikeouts by its inverse. PA stay
+    /// the same so the outs made up the difference.
+    fn translate(&mut self, factor: f32) {
+        let scale_u8 = |stat: u8, factor: f32| (stat as f32 * factor).round() as u8;
+        let scale_u16 = |stat: u16, factor: f32| (stat as f32 * factor).round() as u16;
+        self.r = scale_u8(self.r, factor);
+        self.h = scale_u16(self.h, factor);
+        self.double = scale_u8(self.double, factor);
+        self.triple = scale_u8(self.triple, factor);
+        self.hr = scale_u8(self.hr, factor);
+        self.rbi = scale_u8(self.rbi, factor);
+        self.sb = scale_u8(self.sb, factor);
+        self.bb = scale_u16(self.bb, factor);
+        self.ibb = scale_u8(self.ibb, factor);
+        self.hbp = scale_u8(self.hbp, factor);
+        self.so = scale_u16(self.so, 1.0 / factor);
+        self.fit_to_pa();
+    }
+
+    // Scaled components can add up to more than the PA, such as walks in a short season with a
+    // factor above one. Trim walks, then hits and strikeouts, to what the PA leave room for and
+    // derive AB from what is left.
+    fn fit_to_pa(&mut self) {
+        let cap_u8 = |stat: u8, max: u16| stat.min(max.min(u8::MAX as u16) as u8);
+        let room = self.pa.saturating_sub(self.sf as u16 + self.sh as u16);
+        self.hbp = cap_u8(self.hbp, room);
+        self.bb = self.bb.min(room - self.hbp as u16);
+        self.ibb = cap_u8(self.ibb, self.bb);
+        self.ab = room - (self.bb + self.hbp as u16);
+
+        self.h = self.h.min(self.ab);
+        let mut hits_left = self.h;
+        self.hr = cap_u8(self.hr, hits_left);
+        hits_left -= self.hr as u16;
+        self.triple = cap_u8(self.triple, hits_left);
+        hits_left -= self.triple as u16;
+        self.double = cap_u8(self.double, hits_left);
+        self.so = self.so.min(self.ab - self.h);
+    }
+
     pub fn playerid(&self) -> &String {
         &self.playerid
     }
@@ -543,12 +1155,54 @@ impl BattingSeason {
         &self.yearid
     }
 
+    pub fn pa(&self) -> &u16 {
+        &self.pa
+    }
+
+    pub fn teamid(&self) -> &str {
+        &self.teamid
+    }
+
+    pub fn lgid(&self) -> &str {
+        &self.lgid
+    }
+
     pub fn is_year(&self, year: u16) -> bool {
         self.yearid == year
     }
 }
 
 impl PitchingSeason {
+    /// The season as if it were pitched in a neutral park.
+    pub fn neutralized(&self, park: &ParkFactor) -> Self {
+        let scale_u8 = |stat: u8, factor: f32| (stat as f32 / factor).round() as u8;
+        let scale_u16 = |stat: u16, factor: f32| (stat as f32 / factor).round() as u16;
+        let mut season = self.clone();
+        season.h = scale_u16(self.h, park.h);
+        season.r = scale_u16(self.r, park.r);
+        season.er = scale_u16(self.er, park.r);
+        season.hr = scale_u8(self.hr, park.hr);
+        season.so = scale_u16(self.so, park.so);
+        season.bb = scale_u16(self.bb, park.bb);
+        season.ibb = scale_u8(self.ibb, park.bb);
+        season
+    }
+
+    /// Scale what the pitcher allows by the inverse of `factor` and strikeouts by `factor`.
+    fn translate(&mut self, factor: f32) {
+        let scale_u8 = |stat: u8, factor: f32| (stat as f32 * factor).round() as u8;
+        let scale_u16 = |stat: u16, factor: f32| (stat as f32 * factor).round() as u16;
+        let inverse = 1.0 / factor;
+        self.h = scale_u16(self.h, inverse);
+        self.r = scale_u16(self.r, inverse);
+        self.er = scale_u16(self.er, inverse).min(self.r);
+        self.hr = scale_u8(self.hr, inverse);
+        self.bb = scale_u16(self.bb, inverse);
+        self.ibb = scale_u8(self.ibb, inverse);
+        self.hbp = scale_u8(self.hbp, inverse);
+        self.so = scale_u16(self.so, factor);
+    }
+
     pub fn playerid(&self) -> &String {
         &self.playerid
     }
@@ -557,6 +1211,18 @@ impl PitchingSeason {
         &self.yearid
     }
 
+    pub fn ipouts(&self) -> &u16 {
+        &self.ipouts
+    }
+
+    pub fn teamid(&self) -> &str {
+        &self.teamid
+    }
+
+    pub fn lgid(&self) -> &str {
+        &self.lgid
+    }
+
     pub fn is_year(&self, year: u16) -> bool {
         self.yearid == year
     }
@@ -590,27 +1256,27 @@ impl BattingSeasonSummary {
         &self.pa
     }
 
-    pub fn add_season(&self, season: &BattingSeason) -> Self {
-        BattingSeasonSummary {
-            g: self.g + season.g as u32,
-            pa: self.pa + season.pa as u32,
-            ab: self.ab + season.ab as u32,
-            r: self.r + season.r as u32,
-            h: self.h + season.h as u32,
-            double: self.double + season.double as u32,
-            triple: self.triple + season.triple as u32,
-            hr: self.hr + season.hr as u32,
-            rbi: self.rbi + season.rbi as u32,
-            sb: self.sb + season.sb as u32,
-            cs: self.cs + season.cs as u32,
-            bb: self.bb + season.bb as u32,
-            so: self.so + season.so as u32,
-            ibb: self.ibb + season.ibb as u32,
-            hbp: self.hbp + season.hbp as u32,
-            sh: self.sh + season.sh as u32,
-            sf: self.sf + season.sf as u32,
-            gidp: self.gidp + season.gidp as u32,
-        }
+    pub fn stat(&self, stat: BattingStat) -> f32 {
+        let value = match stat {
+            BattingStat::Pa => self.pa,
+            BattingStat::Ab => self.ab,
+            BattingStat::R => self.r,
+            BattingStat::H => self.h,
+            BattingStat::Double => self.double,
+            BattingStat::Triple => self.triple,
+            BattingStat::Hr => self.hr,
+            BattingStat::Rbi => self.rbi,
+            BattingStat::Sb => self.sb,
+            BattingStat::Cs => self.cs,
+            BattingStat::Bb => self.bb,
+            BattingStat::So => self.so,
+            BattingStat::Ibb => self.ibb,
+            BattingStat::Hbp => self.hbp,
+            BattingStat::Sh => self.sh,
+            BattingStat::Sf => self.sf,
+            BattingStat::Gidp => self.gidp,
+        };
+        value as f32
     }
 
     pub fn mut_add_season(&mut self, season: &BattingSeason) {
@@ -665,24 +1331,23 @@ impl PitchingSeasonSummary {
         (self.g - self.gs) > self.gs
     }
 
-    pub fn add_season(&self, season: &PitchingSeason) -> Self {
-        PitchingSeasonSummary {
-            ipouts: self.ipouts + season.ipouts as u32,
-            w: self.w + season.w as u32,
-            l: self.l + season.l as u32,
-            g: self.g + season.g as u32,
-            gs: self.gs + season.gs as u32,
-            sv: self.sv + season.sv as u32,
-            h: self.h + season.h as u32,
-            r: self.r + season.r as u32,
-            er: self.er + season.er as u32,
-            hr: self.hr + season.hr as u32,
-            so: self.so + season.so as u32,
-            bb: self.bb + season.bb as u32,
-            ibb: self.ibb + season.ibb as u32,
-            hbp: self.hbp + season.hbp as u32,
-            wp: self.wp + season.wp as u32,
-            bk: self.bk + season.bk as u32,
+    pub fn stat(&self, stat: PitchingStat) -> f32 {
+        match stat {
+            // Keep the same units as the projection.
+            PitchingStat::Ip => self.ipouts as f32 / 3.0,
+            PitchingStat::W => self.w as f32,
+            PitchingStat::L => self.l as f32,
+            PitchingStat::Sv => self.sv as f32,
+            PitchingStat::H => self.h as f32,
+            PitchingStat::R => self.r as f32,
+            PitchingStat::Er => self.er as f32,
+            PitchingStat::Hr => self.hr as f32,
+            PitchingStat::So => self.so as f32,
+            PitchingStat::Bb => self.bb as f32,
+            PitchingStat::Ibb => self.ibb as f32,
+            PitchingStat::Hbp => self.hbp as f32,
+            PitchingStat::Wp => self.wp as f32,
+            PitchingStat::Bk => self.bk as f32,
         }
     }
 
@@ -706,6 +1371,74 @@ impl PitchingSeasonSummary {
     }
 }
 
+impl BattingSeasonSummaryRates {
+    pub fn pa(&self) -> &u32 {
+        &self.pa
+    }
+
+    /// Whether there were PA to take the rates from and every rate is a number.
+    pub fn is_finite(&self) -> bool {
+        self.pa > 0 && BattingStat::ALL.iter()
+            .filter_map(|stat| self.rate(*stat))
+            .all(f32::is_finite)
+    }
+
+    /// The league rate of a stat per PA. PA and AB have no rate.
+    pub fn rate(&self, stat: BattingStat) -> Option<f32> {
+        match stat {
+            BattingStat::Pa | BattingStat::Ab => None,
+            BattingStat::R => Some(self.r),
+            BattingStat::H => Some(self.h),
+            BattingStat::Double => Some(self.double),
+            BattingStat::Triple => Some(self.triple),
+            BattingStat::Hr => Some(self.hr),
+            BattingStat::Rbi => Some(self.rbi),
+            BattingStat::Sb => Some(self.sb),
+            BattingStat::Cs => Some(self.cs),
+            BattingStat::Bb => Some(self.bb),
+            BattingStat::So => Some(self.so),
+            BattingStat::Ibb => Some(self.ibb),
+            BattingStat::Hbp => Some(self.hbp),
+            BattingStat::Sh => Some(self.sh),
+            BattingStat::Sf => Some(self.sf),
+            BattingStat::Gidp => Some(self.gidp),
+        }
+    }
+}
+
+impl PitchingSeasonSummaryRates {
+    pub fn ipouts(&self) -> &u32 {
+        &self.ipouts
+    }
+
+    /// Whether there were outs to take the rates from and every rate is a number.
+    pub fn is_finite(&self) -> bool {
+        self.ipouts > 0 && PitchingStat::ALL.iter()
+            .filter_map(|stat| self.rate(*stat))
+            .all(f32::is_finite)
+    }
+
+    /// The league rate of a stat per out. IP has no rate.
+    pub fn rate(&self, stat: PitchingStat) -> Option<f32> {
+        match stat {
+            PitchingStat::Ip => None,
+            PitchingStat::W => Some(self.w),
+            PitchingStat::L => Some(self.l),
+            PitchingStat::Sv => Some(self.sv),
+            PitchingStat::H => Some(self.h),
+            PitchingStat::R => Some(self.r),
+            PitchingStat::Er => Some(self.er),
+            PitchingStat::Hr => Some(self.hr),
+            PitchingStat::So => Some(self.so),
+            PitchingStat::Bb => Some(self.bb),
+            PitchingStat::Ibb => Some(self.ibb),
+            PitchingStat::Hbp => Some(self.hbp),
+            PitchingStat::Wp => Some(self.wp),
+            PitchingStat::Bk => Some(self.bk),
+        }
+    }
+}
+
 impl From<BattingSeasonSummary> for BattingSeasonSummaryRates {
     fn from(summary: BattingSeasonSummary) -> BattingSeasonSummaryRates {
         let pa_f = summary.pa as f32;
@@ -753,12 +1486,14 @@ impl From<PitchingSeasonSummary> for PitchingSeasonSummaryRates {
 }
 
 impl IntBattingProjection {
-    pub fn new_player(playerid: &String, year: u16) -> Self {
+    pub fn new_player(playerid: &str, year: u16) -> Self {
         IntBattingProjection {
-            playerid: playerid.clone(),
-            age: 0,
-            year: year,
+            playerid: playerid.to_string(),
+            age: 0.0,
+            age_convention: "",
+            year,
             reliability: 0.0,
+            sample: 0.0,
             pa: 0.0,
             ab: 0.0,
             r: 0.0,
@@ -782,9 +1517,11 @@ impl IntBattingProjection {
     pub fn league() -> Self {
         IntBattingProjection {
             playerid: String::from(""),
-            age: 0,
+            age: 0.0,
+            age_convention: "",
             year: 0,
             reliability: 0.0,
+            sample: 0.0,
             pa: 0.0,
             ab: 0.0,
             r: 0.0,
@@ -805,24 +1542,72 @@ impl IntBattingProjection {
         }
     }
 
-    pub fn regress(&mut self, proj: &Self) {
-        self.reliability = self.pa / (self.pa + proj.pa);
-        self.pa += proj.pa;
-        self.r += proj.r;
-        self.h += proj.h;
-        self.double += proj.double;
-        self.triple += proj.triple;
-        self.hr += proj.hr;
-        self.rbi += proj.rbi;
-        self.sb += proj.sb;
-        self.cs += proj.cs;
-        self.bb += proj.bb;
-        self.so += proj.so;
-        self.ibb += proj.ibb;
-        self.hbp += proj.hbp;
-        self.sh += proj.sh;
-        self.sf += proj.sf;
-        self.gidp += proj.gidp;
+    pub fn stat(&self, stat: BattingStat) -> f32 {
+        match stat {
+            BattingStat::Pa => self.pa,
+            BattingStat::Ab => self.ab,
+            BattingStat::R => self.r,
+            BattingStat::H => self.h,
+            BattingStat::Double => self.double,
+            BattingStat::Triple => self.triple,
+            BattingStat::Hr => self.hr,
+            BattingStat::Rbi => self.rbi,
+            BattingStat::Sb => self.sb,
+            BattingStat::Cs => self.cs,
+            BattingStat::Bb => self.bb,
+            BattingStat::So => self.so,
+            BattingStat::Ibb => self.ibb,
+            BattingStat::Hbp => self.hbp,
+            BattingStat::Sh => self.sh,
+            BattingStat::Sf => self.sf,
+            BattingStat::Gidp => self.gidp,
+        }
+    }
+
+    fn stat_mut(&mut self, stat: BattingStat) -> &mut f32 {
+        match stat {
+            BattingStat::Pa => &mut self.pa,
+            BattingStat::Ab => &mut self.ab,
+            BattingStat::R => &mut self.r,
+            BattingStat::H => &mut self.h,
+            BattingStat::Double => &mut self.double,
+            BattingStat::Triple => &mut self.triple,
+            BattingStat::Hr => &mut self.hr,
+            BattingStat::Rbi => &mut self.rbi,
+            BattingStat::Sb => &mut self.sb,
+            BattingStat::Cs => &mut self.cs,
+            BattingStat::Bb => &mut self.bb,
+            BattingStat::So => &mut self.so,
+            BattingStat::Ibb => &mut self.ibb,
+            BattingStat::Hbp => &mut self.hbp,
+            BattingStat::Sh => &mut self.sh,
+            BattingStat::Sf => &mut self.sf,
+            BattingStat::Gidp => &mut self.gidp,
+        }
+    }
+
+    /// Regress the player toward `proj`, a league mean prorated to the overall regression amount.
+    /// Components found in `components` are instead regressed by their own number of PA, so stats
+    /// that stabilize quickly lean more on the player. The result keeps a denominator of the
+    /// player's PA plus the overall amount so it can be prorated like any other projection.
+    pub fn regress(&mut self, proj: &Self, components: &BTreeMap<BattingStat, f32>) {
+        let player_pa = self.pa;
+        let total_pa = player_pa + proj.pa;
+        for stat in BattingStat::ALL.iter().filter(|stat| is_regressed_batting_stat(**stat)) {
+            let league = proj.stat(*stat);
+            let player = self.stat_mut(*stat);
+            match components.get(stat) {
+                Some(amount) if proj.pa > 0.0 => {
+                    let league_rate = league / proj.pa;
+                    let rate = (*player + league_rate * amount) / (player_pa + amount);
+                    *player = rate * total_pa;
+                }
+                _ => *player += league,
+            }
+        }
+        self.reliability = self.pa / total_pa;
+        self.sample = total_pa;
+        self.pa = total_pa;
         self.ab = self.pa - (self.bb + self.hbp + self.sf + self.sh);
     }
 
@@ -846,6 +1631,26 @@ impl IntBattingProjection {
         self.ab = self.pa - (self.bb + self.hbp + self.sf + self.sh);
     }
 
+    pub fn add(&mut self, other: &Self) {
+        self.pa += other.pa;
+        self.r += other.r;
+        self.h += other.h;
+        self.double += other.double;
+        self.triple += other.triple;
+        self.hr += other.hr;
+        self.rbi += other.rbi;
+        self.sb += other.sb;
+        self.cs += other.cs;
+        self.bb += other.bb;
+        self.so += other.so;
+        self.ibb += other.ibb;
+        self.hbp += other.hbp;
+        self.sh += other.sh;
+        self.sf += other.sf;
+        self.gidp += other.gidp;
+        self.ab = self.pa - (self.bb + self.hbp + self.sf + self.sh);
+    }
+
     pub fn weighted_rate_add(&mut self, pa: u16, rates: &BattingSeasonSummaryRates, weight: f32) {
         let pa_f = pa as f32;
         self.pa += pa_f * weight;
@@ -872,9 +1677,11 @@ impl IntBattingProjection {
         let pa_factor = pa_f / self.pa;
         let mut proj = IntBattingProjection {
             playerid: self.playerid.clone(),
-            age: 0,
+            age: 0.0,
+            age_convention: "",
             year: self.year,
             reliability: self.reliability,
+            sample: self.sample,
             pa: pa_f,
             ab: 0.0,
             r: self.r * pa_factor,
@@ -895,43 +1702,82 @@ impl IntBattingProjection {
         };
 
         proj.ab = proj.pa - (proj.bb + proj.hbp + proj.sf + proj.sh);
-        return proj;
+        proj
     }
 
-    pub fn set_age(&mut self, age: u8) {
+    /// Set the player's age and the name of the convention used to calculate it.
+    pub fn set_age(&mut self, age: f32, convention: &'static str) {
         self.age = age;
+        self.age_convention = convention;
+    }
+
+    pub fn adjust(&mut self, stat: BattingStat, amount: f32) {
+        *self.stat_mut(stat) *= amount;
     }
 
-    pub fn age_adjust(&mut self, amount: f32) {
-        self.r *= amount;
-        self.h *= amount;
-        self.double *= amount;
-        self.triple *= amount;
-        self.hr *= amount;
-        self.rbi *= amount;
-        self.sb *= amount;
-        self.cs *= amount;
-        self.bb *= amount;
-        self.so *= amount;
-        self.ibb *= amount;
-        self.hbp *= amount;
-        self.sh *= amount;
-        self.sf *= amount;
-        self.gidp *= amount;
-    }
-
-    pub fn finalize(self) -> BattingProjection {
+    /// Move a neutral projection into the player's park.
+    pub fn apply_park(&mut self, park: &ParkFactor) {
+        let singles = self.h - (self.double + self.triple + self.hr);
+        self.r *= park.r;
+        self.rbi *= park.r;
+        self.double *= park.double;
+        self.triple *= park.triple;
+        self.hr *= park.hr;
+        self.h = singles * park.h + self.double + self.triple + self.hr;
+        self.so *= park.so;
+        self.bb *= park.bb;
+        self.ibb *= park.bb;
+        self.ab = self.pa - (self.bb + self.hbp + self.sf + self.sh);
+    }
+
+    /// Round the components for the projection and compute the `rates` wanted from the unrounded
+    /// components. wOBA and the stats built on it need `weights`.
+    pub fn finalize(self, rates: &[BattingRate], weights: Option<&LinearWeights>)
+        -> BattingProjection
+    {
         let pa = self.pa.round();
         let bb = self.bb.round();
         let hbp = self.hbp.round();
         let sf = self.sf.round();
         let sh = self.sh.round();
+        let rate = |rate: BattingRate| {
+            if rates.contains(&rate) {
+                self.rate(rate, weights)
+            }
+            else {
+                None
+            }
+        };
+        let avg = rate(BattingRate::Avg);
+        let obp = rate(BattingRate::Obp);
+        let slg = rate(BattingRate::Slg);
+        let ops = rate(BattingRate::Ops);
+        let iso = rate(BattingRate::Iso);
+        let babip = rate(BattingRate::Babip);
+        let k_pct = rate(BattingRate::KPct);
+        let bb_pct = rate(BattingRate::BbPct);
+        let woba = rate(BattingRate::Woba);
+        let wraa = rate(BattingRate::Wraa);
+        let wrc_plus = rate(BattingRate::WrcPlus);
         BattingProjection {
             playerid: self.playerid,
+            name_first: None,
+            name_last: None,
+            bats: None,
+            throws: None,
+            key_mlbam: None,
+            key_retro: None,
+            key_fangraphs: None,
+            debut: None,
+            teamid: String::new(),
+            lgid: String::new(),
+            two_way: None,
             age: self.age,
+            age_convention: self.age_convention,
             year: self.year,
             reliability: self.reliability,
-            pa: pa,
+            sample: self.sample,
+            pa,
             r: self.r.round(),
             h: self.h.round(),
             double: self.double.round(),
@@ -940,18 +1786,164 @@ impl IntBattingProjection {
             rbi: self.rbi.round(),
             sb: self.sb.round(),
             cs: self.cs.round(),
-            bb: bb,
+            bb,
             so: self.so.round(),
             ibb: self.ibb.round(),
-            hbp: hbp,
-            sh: sh,
-            sf: sf,
+            hbp,
+            sh,
+            sf,
             gidp: self.gidp.round(),
             ab: pa - (bb + hbp + sf + sh),
+            avg,
+            obp,
+            slg,
+            ops,
+            iso,
+            babip,
+            k_pct,
+            bb_pct,
+            woba,
+            wraa,
+            wrc_plus,
+        }
+    }
+
+    // Rates with nothing to divide by are zero so every row has the same columns. Only the wOBA
+    // stats can be missing, when there are no weights for the year.
+    fn rate(&self, rate: BattingRate, weights: Option<&LinearWeights>) -> Option<f32> {
+        let ratio = |numerator: f32, denominator: f32| {
+            if denominator > 0.0 { numerator / denominator } else { 0.0 }
+        };
+        // Aging can change BB, HBP, SF and SH after AB was last set.
+        let ab = self.pa - (self.bb + self.hbp + self.sf + self.sh);
+        let singles = self.h - (self.double + self.triple + self.hr);
+        let total_bases = singles + 2.0 * self.double + 3.0 * self.triple + 4.0 * self.hr;
+        let avg = ratio(self.h, ab);
+        let obp = ratio(self.h + self.bb + self.hbp, ab + self.bb + self.hbp + self.sf);
+        let slg = ratio(total_bases, ab);
+        let woba = |weights: &LinearWeights| {
+            let unintentional_bb = self.bb - self.ibb;
+            ratio(weights.bb * unintentional_bb + weights.hbp * self.hbp + weights.single * singles
+                  + weights.double * self.double + weights.triple * self.triple
+                  + weights.hr * self.hr,
+                  ab + unintentional_bb + self.sf + self.hbp)
+        };
+        let wraa = |weights: &LinearWeights| {
+            (woba(weights) - weights.woba) / weights.scale * self.pa
+        };
+        match rate {
+            BattingRate::Avg => Some(avg),
+            BattingRate::Obp => Some(obp),
+            BattingRate::Slg => Some(slg),
+            BattingRate::Ops => Some(obp + slg),
+            BattingRate::Iso => Some(slg - avg),
+            BattingRate::Babip => Some(ratio(self.h - self.hr, ab - self.so - self.hr + self.sf)),
+            BattingRate::KPct => Some(ratio(self.so, self.pa)),
+            BattingRate::BbPct => Some(ratio(self.bb, self.pa)),
+            BattingRate::Woba => weights.map(woba),
+            BattingRate::Wraa => weights.map(wraa),
+            BattingRate::WrcPlus => weights.map(|weights| {
+                let runs_per_pa = ratio(wraa(weights), self.pa) + weights.runs_per_pa;
+                100.0 * ratio(runs_per_pa, weights.runs_per_pa)
+            }),
         }
     }
 }
 
+impl BattingProjection {
+    pub fn playerid(&self) -> &String {
+        &self.playerid
+    }
+
+    /// Weighted PA behind the projected rates, including the regression to the league.
+    pub fn sample(&self) -> f32 {
+        self.sample
+    }
+
+    /// Set the teams and leagues from the player's most recent season. Players traded during
+    /// the season list each in order, separated by slashes.
+    pub fn set_team(&mut self, stints: &[Stint]) {
+        let (teamid, lgid) = join_stints(stints);
+        self.teamid = teamid;
+        self.lgid = lgid;
+    }
+
+    /// Flag players that both bat and pitch.
+    pub fn set_two_way(&mut self, two_way: bool) {
+        self.two_way = Some(two_way);
+    }
+
+    pub fn set_player_info(&mut self, info: PlayerInfo) {
+        self.name_first = Some(info.name_first);
+        self.name_last = Some(info.name_last);
+        self.bats = Some(info.bats);
+        self.throws = Some(info.throws);
+        self.key_mlbam = Some(info.key_mlbam);
+        self.key_retro = Some(info.key_retro);
+        self.key_fangraphs = Some(info.key_fangraphs);
+        self.debut = Some(info.debut);
+    }
+
+    pub fn stat(&self, stat: BattingStat) -> f32 {
+        match stat {
+            BattingStat::Pa => self.pa,
+            BattingStat::Ab => self.ab,
+            BattingStat::R => self.r,
+            BattingStat::H => self.h,
+            BattingStat::Double => self.double,
+            BattingStat::Triple => self.triple,
+            BattingStat::Hr => self.hr,
+            BattingStat::Rbi => self.rbi,
+            BattingStat::Sb => self.sb,
+            BattingStat::Cs => self.cs,
+            BattingStat::Bb => self.bb,
+            BattingStat::So => self.so,
+            BattingStat::Ibb => self.ibb,
+            BattingStat::Hbp => self.hbp,
+            BattingStat::Sh => self.sh,
+            BattingStat::Sf => self.sf,
+            BattingStat::Gidp => self.gidp,
+        }
+    }
+}
+
+fn level_column(headers: &csv::StringRecord) -> Result<usize, csv::Error> {
+    headers.iter()
+        .position(|header| header.eq_ignore_ascii_case("level"))
+        .ok_or_else(|| invalid_data("No level column in the minor league data".to_string()))
+}
+
+// Ages are whole years except under the exact convention, so leave off the fraction when there is
+// none.
+fn serialize_age<S: Serializer>(age: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    if age.fract() == 0.0 && (0.0..=u8::MAX as f32).contains(age) {
+        serializer.serialize_u8(*age as u8)
+    }
+    else {
+        serializer.serialize_f32(*age)
+    }
+}
+
+fn invalid_data(message: String) -> csv::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message).into()
+}
+
+fn join_stints(stints: &[Stint]) -> (String, String) {
+    let teams: Vec<&str> = stints.iter().map(|stint| stint.teamid()).collect();
+    let mut leagues: Vec<&str> = Vec::new();
+    for stint in stints {
+        if !leagues.contains(&stint.lgid()) {
+            leagues.push(stint.lgid());
+        }
+    }
+    (teams.join("/"), leagues.join("/"))
+}
+
+// PA is the denominator and AB is derived from the other components.
+fn is_regressed_batting_stat(stat: BattingStat) -> bool {
+    stat != BattingStat::Pa && stat != BattingStat::Ab
+}
+
 impl Eq for BattingProjection {}
 
 impl Ord for BattingProjection {
@@ -973,12 +1965,14 @@ impl PartialEq for BattingProjection {
 }
 
 impl IntPitchingProjection {
-    pub fn new_player(playerid: &String, year: u16) -> Self {
+    pub fn new_player(playerid: &str, year: u16) -> Self {
         IntPitchingProjection {
-            playerid: playerid.clone(),
-            age: 0,
-            year: year,
+            playerid: playerid.to_string(),
+            age: 0.0,
+            age_convention: "",
+            year,
             reliability: 0.0,
+            sample: 0.0,
             ipouts: 0.0,
             w: 0.0,
             l: 0.0,
@@ -999,9 +1993,11 @@ impl IntPitchingProjection {
     pub fn league() -> Self {
         IntPitchingProjection {
             playerid: String::from(""),
-            age: 0,
+            age: 0.0,
+            age_convention: "",
             year: 0,
             reliability: 0.0,
+            sample: 0.0,
             ipouts: 0.0,
             w: 0.0,
             l: 0.0,
@@ -1019,22 +2015,65 @@ impl IntPitchingProjection {
         }
     }
 
-    pub fn regress(&mut self, proj: &Self) {
-        self.reliability = self.ipouts / (self.ipouts + proj.ipouts);
-        self.ipouts += proj.ipouts;
-        self.w += proj.w;
-        self.l += proj.l;
-        self.sv += proj.sv;
-        self.h += proj.h;
-        self.r += proj.r;
-        self.er += proj.er;
-        self.hr += proj.hr;
-        self.so += proj.so;
-        self.bb += proj.bb;
-        self.ibb += proj.ibb;
-        self.hbp += proj.hbp;
-        self.wp += proj.wp;
-        self.bk += proj.bk;
+    pub fn stat(&self, stat: PitchingStat) -> f32 {
+        match stat {
+            PitchingStat::Ip => self.ipouts,
+            PitchingStat::W => self.w,
+            PitchingStat::L => self.l,
+            PitchingStat::Sv => self.sv,
+            PitchingStat::H => self.h,
+            PitchingStat::R => self.r,
+            PitchingStat::Er => self.er,
+            PitchingStat::Hr => self.hr,
+            PitchingStat::So => self.so,
+            PitchingStat::Bb => self.bb,
+            PitchingStat::Ibb => self.ibb,
+            PitchingStat::Hbp => self.hbp,
+            PitchingStat::Wp => self.wp,
+            PitchingStat::Bk => self.bk,
+        }
+    }
+
+    fn stat_mut(&mut self, stat: PitchingStat) -> &mut f32 {
+        match stat {
+            PitchingStat::Ip => &mut self.ipouts,
+            PitchingStat::W => &mut self.w,
+            PitchingStat::L => &mut self.l,
+            PitchingStat::Sv => &mut self.sv,
+            PitchingStat::H => &mut self.h,
+            PitchingStat::R => &mut self.r,
+            PitchingStat::Er => &mut self.er,
+            PitchingStat::Hr => &mut self.hr,
+            PitchingStat::So => &mut self.so,
+            PitchingStat::Bb => &mut self.bb,
+            PitchingStat::Ibb => &mut self.ibb,
+            PitchingStat::Hbp => &mut self.hbp,
+            PitchingStat::Wp => &mut self.wp,
+            PitchingStat::Bk => &mut self.bk,
+        }
+    }
+
+    /// Regress the player toward `proj`, a league mean prorated to the overall regression amount.
+    /// Components found in `components` are instead regressed by their own number of outs. See
+    /// `IntBattingProjection::regress`.
+    pub fn regress(&mut self, proj: &Self, components: &BTreeMap<PitchingStat, f32>) {
+        let player_ipouts = self.ipouts;
+        let total_ipouts = player_ipouts + proj.ipouts;
+        for stat in PitchingStat::ALL.iter().filter(|stat| **stat != PitchingStat::Ip) {
+            let league = proj.stat(*stat);
+            let player = self.stat_mut(*stat);
+            match components.get(stat) {
+                Some(amount) if proj.ipouts > 0.0 => {
+                    let league_rate = league / proj.ipouts;
+                    let rate = (*player + league_rate * amount) / (player_ipouts + amount);
+                    *player = rate * total_ipouts;
+                }
+                _ => *player += league,
+            }
+        }
+        self.reliability = self.ipouts / total_ipouts;
+        self.sample = total_ipouts;
+        self.ipouts = total_ipouts;
     }
 
     pub fn weighted_add(&mut self, season: &PitchingSeasonSummary, weight: f32) {
@@ -1054,6 +2093,23 @@ impl IntPitchingProjection {
         self.bk += season.bk as f32 * weight;
     }
 
+    pub fn add(&mut self, other: &Self) {
+        self.ipouts += other.ipouts;
+        self.w += other.w;
+        self.l += other.l;
+        self.sv += other.sv;
+        self.h += other.h;
+        self.r += other.r;
+        self.er += other.er;
+        self.hr += other.hr;
+        self.so += other.so;
+        self.bb += other.bb;
+        self.ibb += other.ibb;
+        self.hbp += other.hbp;
+        self.wp += other.wp;
+        self.bk += other.bk;
+    }
+
     pub fn weighted_rate_add(&mut self, ipouts: u16, rates: &PitchingSeasonSummaryRates, weight: f32) {
         let ip_f = ipouts as f32;
         self.ipouts += ip_f * weight;
@@ -1077,9 +2133,11 @@ impl IntPitchingProjection {
         let ip_factor = ip_f / self.ipouts;
         IntPitchingProjection {
             playerid: self.playerid.clone(),
-            age: 0,
+            age: 0.0,
+            age_convention: "",
             year: self.year,
             reliability: self.reliability,
+            sample: self.sample,
             ipouts: ip_f,
             w: self.w * ip_factor,
             l: self.l * ip_factor,
@@ -1097,37 +2155,73 @@ impl IntPitchingProjection {
         }
     }
 
-    pub fn set_age(&mut self, age: u8) {
+    /// Set the player's age and the name of the convention used to calculate it.
+    pub fn set_age(&mut self, age: f32, convention: &'static str) {
         self.age = age;
+        self.age_convention = convention;
     }
 
-    pub fn age_adjust(&mut self, amount: f32) {
-        self.ipouts *= amount;
-        self.w *= amount;
-        self.l *= amount;
-        self.sv *= amount;
-        self.h *= amount;
-        self.r *= amount;
-        self.er *= amount;
-        self.hr *= amount;
-        self.so *= amount;
-        self.bb *= amount;
-        self.ibb *= amount;
-        self.hbp *= amount;
-        self.wp *= amount;
-        self.bk *= amount;
-    }
-
-    pub fn finalize(self) -> PitchingProjection {
+    pub fn adjust(&mut self, stat: PitchingStat, amount: f32) {
+        *self.stat_mut(stat) *= amount;
+    }
+
+    /// Move a neutral projection into the pitcher's park.
+    pub fn apply_park(&mut self, park: &ParkFactor) {
+        self.h *= park.h;
+        self.r *= park.r;
+        self.er *= park.r;
+        self.hr *= park.hr;
+        self.so *= park.so;
+        self.bb *= park.bb;
+        self.ibb *= park.bb;
+    }
+
+    /// Round the components for the projection and compute the `rates` wanted from the unrounded
+    /// components. FIP and xFIP need `constants`. ERA blends the projected earned runs with Base
+    /// Runs, giving Base Runs a share of `era_blend`.
+    pub fn finalize(self, rates: &[PitchingRate], constants: Option<&FipConstants>,
+                    baseruns: &BaseRuns, era_blend: f32) -> PitchingProjection
+    {
         // Internally using outs so turn that back into innings for the projection.
         let final_ip = self.ipouts / 3.0;
         let final_ip = final_ip.round();
-        let bsrer = self.calculate_baseruns();
+        // Base Runs estimate of earned runs.
+        let raw_bsrer = baseruns.runs(self.h, self.hr, self.bb, self.ipouts);
+        let bsrer = raw_bsrer.round();
+        let rate = |rate: PitchingRate| {
+            if rates.contains(&rate) {
+                self.rate(rate, constants)
+            }
+            else {
+                None
+            }
+        };
+        let fip = rate(PitchingRate::Fip);
+        let xfip = rate(PitchingRate::Xfip);
+        let whip = rate(PitchingRate::Whip);
+        let k_9 = rate(PitchingRate::K9);
+        let bb_9 = rate(PitchingRate::Bb9);
+        let hr_9 = rate(PitchingRate::Hr9);
+        let k_pct = rate(PitchingRate::KPct);
+        let bb_pct = rate(PitchingRate::BbPct);
         PitchingProjection {
             playerid: self.playerid,
+            name_first: None,
+            name_last: None,
+            bats: None,
+            throws: None,
+            key_mlbam: None,
+            key_retro: None,
+            key_fangraphs: None,
+            debut: None,
+            teamid: String::new(),
+            lgid: String::new(),
+            two_way: None,
             age: self.age,
+            age_convention: self.age_convention,
             year: self.year,
             reliability: self.reliability,
+            sample: self.sample,
             ip: final_ip,
             w: self.w.round(),
             l: self.l.round(),
@@ -1135,10 +2229,11 @@ impl IntPitchingProjection {
             h: self.h.round(),
             r: self.r.round(),
             er: self.er.round(),
-            bsrer: bsrer,
-            // 50-50 split between projected earned runs and Base Runs estimation of earned runs.
+            bsrer,
             // Ideally round the ERA to two digits of precision.
-            era: (self.er + bsrer) * 9.0 / (2.0 * final_ip),
+            era: ((1.0 - era_blend) * self.er + era_blend * bsrer) * 9.0 / final_ip,
+            raw_er: self.er,
+            raw_bsrer,
             hr: self.hr.round(),
             so: self.so.round(),
             bb: self.bb.round(),
@@ -1146,18 +2241,101 @@ impl IntPitchingProjection {
             hbp: self.hbp.round(),
             wp: self.wp.round(),
             bk: self.bk.round(),
+            fip,
+            xfip,
+            whip,
+            k_9,
+            bb_9,
+            hr_9,
+            k_pct,
+            bb_pct,
         }
     }
 
-    // Calculate base runs estimate of earned runs.
-    fn calculate_baseruns(&self) -> f32 {
-        let a = self.h + self.bb - self.hr;
-        let b = (1.4 * (1.12 * self.h + 4.0 * self.hr) - 0.6 * self.h - 3.0 * self.hr + 0.1 * self.bb) * 1.1;
-        // This is 3 * IP but we already have the number of outs.
-        let c = self.ipouts;
-        let d = self.hr;
-        let baseruns = (a * b) / (b + c) + d;
-        baseruns.round()
+    // Rates with nothing to divide by are zero so every row has the same columns. Only FIP and
+    // xFIP can be missing, when there are no constants for the year.
+    fn rate(&self, rate: PitchingRate, constants: Option<&FipConstants>) -> Option<f32> {
+        let ratio = |numerator: f32, denominator: f32| {
+            if denominator > 0.0 { numerator / denominator } else { 0.0 }
+        };
+        let ip = self.ipouts / 3.0;
+        let batters_faced = self.ipouts + self.h + self.bb + self.hbp;
+        let fip = |hr: f32, constants: &FipConstants| {
+            ratio(FIP_HR * hr + FIP_BB * (self.bb + self.hbp) - FIP_SO * self.so, ip)
+                + constants.constant
+        };
+        match rate {
+            PitchingRate::Fip => constants.map(|constants| fip(self.hr, constants)),
+            // xFIP gives the pitcher a league average home run rate on their balls in play.
+            PitchingRate::Xfip => constants.map(|constants| {
+                let contact = (self.ipouts - self.so + self.h).max(0.0);
+                fip(contact * constants.hr_per_contact, constants)
+            }),
+            PitchingRate::Whip => Some(ratio(self.h + self.bb, ip)),
+            PitchingRate::K9 => Some(9.0 * ratio(self.so, ip)),
+            PitchingRate::Bb9 => Some(9.0 * ratio(self.bb, ip)),
+            PitchingRate::Hr9 => Some(9.0 * ratio(self.hr, ip)),
+            PitchingRate::KPct => Some(ratio(self.so, batters_faced)),
+            PitchingRate::BbPct => Some(ratio(self.bb, batters_faced)),
+        }
+    }
+}
+
+impl PitchingProjection {
+    pub fn playerid(&self) -> &String {
+        &self.playerid
+    }
+
+    /// Weighted outs behind the projected rates, including the regression to the league.
+    pub fn sample(&self) -> f32 {
+        self.sample
+    }
+
+    /// Set the teams and leagues from the player's most recent season. Players traded during
+    /// the season list each in order, separated by slashes.
+    pub fn set_team(&mut self, stints: &[Stint]) {
+        let (teamid, lgid) = join_stints(stints);
+        self.teamid = teamid;
+        self.lgid = lgid;
+    }
+
+    /// Flag players that both bat and pitch.
+    pub fn set_two_way(&mut self, two_way: bool) {
+        self.two_way = Some(two_way);
+    }
+
+    pub fn set_player_info(&mut self, info: PlayerInfo) {
+        self.name_first = Some(info.name_first);
+        self.name_last = Some(info.name_last);
+        self.bats = Some(info.bats);
+        self.throws = Some(info.throws);
+        self.key_mlbam = Some(info.key_mlbam);
+        self.key_retro = Some(info.key_retro);
+        self.key_fangraphs = Some(info.key_fangraphs);
+        self.debut = Some(info.debut);
+    }
+
+    pub fn era(&self) -> f32 {
+        self.era
+    }
+
+    pub fn stat(&self, stat: PitchingStat) -> f32 {
+        match stat {
+            PitchingStat::Ip => self.ip,
+            PitchingStat::W => self.w,
+            PitchingStat::L => self.l,
+            PitchingStat::Sv => self.sv,
+            PitchingStat::H => self.h,
+            PitchingStat::R => self.r,
+            PitchingStat::Er => self.er,
+            PitchingStat::Hr => self.hr,
+            PitchingStat::So => self.so,
+            PitchingStat::Bb => self.bb,
+            PitchingStat::Ibb => self.ibb,
+            PitchingStat::Hbp => self.hbp,
+            PitchingStat::Wp => self.wp,
+            PitchingStat::Bk => self.bk,
+        }
     }
 }
 
@@ -1181,26 +2359,261 @@ impl PartialEq for PitchingProjection {
     }
 }
 
-pub fn write_batting_projection(projections: &Vec<BattingProjection>, year: u16) -> Result<(), csv::Error> {
-    let output_file = format!("BattingCapuchin{}.csv", year);
-    let output_path = Path::new(&output_file);
-    let mut wtr = csv::Writer::from_path(&output_path)?;
+pub fn write_batting_projection(projections: &[BattingProjection], year: u16,
+                                format: OutputFormat, metadata: &Metadata) -> Result<(), output::Error> {
+    output::write(projections, &format!("BattingCapuchin{}", year), format, metadata)
+}
+
+pub fn write_pitching_projection(projections: &[PitchingProjection], year: u16,
+                                 format: OutputFormat, metadata: &Metadata) -> Result<(), output::Error> {
+    output::write(projections, &format!("PitchingCapuchin{}", year), format, metadata)
+}
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
 
-    for projection in projections {
-        let _result = wtr.serialize(projection)?;
+    fn batting_season(pa: u16, h: u16, bb: u16, so: u16) -> BattingSeason {
+        BattingSeason {
+            playerid: "test01".to_string(),
+            yearid: 2015,
+            stint: 1,
+            teamid: "SEA".to_string(),
+            lgid: "AL".to_string(),
+            g: 10,
+            pa,
+            ab: pa - bb,
+            r: 0,
+            h,
+            double: 0,
+            triple: 0,
+            hr: 0,
+            rbi: 0,
+            sb: 0,
+            cs: 0,
+            bb,
+            so,
+            ibb: 0,
+            hbp: 0,
+            sh: 0,
+            sf: 0,
+            gidp: 0,
+        }
     }
 
-    Ok(())
-}
+    fn park(bb: f32, so: f32) -> ParkFactor {
+        ParkFactor { r: 1.0, h: 1.0, double: 1.0, triple: 1.0, hr: 1.0, so, bb }
+    }
+
+    #[test]
+    fn neutralized_walks_fit_in_pa() {
+        let season = batting_season(10, 0, 10, 0).neutralized(&park(0.8, 1.0));
+        assert_eq!(season.bb, 10);
+        assert_eq!(season.ab, 0);
+    }
+
+    #[test]
+    fn neutralized_strikeouts_fit_in_ab() {
+        let season = batting_season(10, 2, 0, 8).neutralized(&park(1.0, 0.8));
+        assert_eq!(season.ab, 10);
+        assert_eq!(season.so, 8);
+    }
+
+    // Roughly the 2015 MLB totals.
+    pub(crate) fn league_batting() -> BattingSeasonSummary {
+        BattingSeasonSummary {
+            g: 4858,
+            pa: 183628,
+            ab: 165521,
+            r: 20647,
+            h: 42106,
+            double: 8242,
+            triple: 939,
+            hr: 4909,
+            rbi: 19650,
+            sb: 2505,
+            cs: 1064,
+            bb: 14073,
+            so: 37446,
+            ibb: 951,
+            hbp: 1602,
+            sh: 1200,
+            sf: 1232,
+            gidp: 3500,
+        }
+    }
+
+    #[test]
+    fn league_hitter_is_average() {
+        let rates = BattingSeasonSummaryRates::from(league_batting());
+        let weights = LinearWeights::from_rates(&rates).unwrap();
+        let mut proj = IntBattingProjection::new_player("test01", 2016);
+        proj.weighted_rate_add(600, &rates, 1.0);
+        let proj = proj.finalize(&BattingRate::ALL, Some(&weights));
+        let obp = proj.obp.unwrap();
+        assert!((weights.woba - obp).abs() < 1e-5);
+        assert!((proj.woba.unwrap() - obp).abs() < 1e-5);
+        assert!(proj.wraa.unwrap().abs() < 1e-3);
+        assert!((proj.wrc_plus.unwrap() - 100.0).abs() < 1e-3);
+    }
+
+    // Roughly the 2015 MLB totals.
+    pub(crate) fn league_pitching() -> PitchingSeasonSummary {
+        PitchingSeasonSummary {
+            ipouts: 130000,
+            w: 2429,
+            l: 2429,
+            g: 14000,
+            gs: 4858,
+            sv: 1290,
+            h: 42106,
+            r: 20647,
+            er: 19000,
+            hr: 4909,
+            so: 37446,
+            bb: 14073,
+            ibb: 951,
+            hbp: 1602,
+            wp: 1500,
+            bk: 150,
+        }
+    }
+
+    #[test]
+    fn league_pitcher_fip_matches_era() {
+        let rates = PitchingSeasonSummaryRates::from(league_pitching());
+        let constants = FipConstants::from_rates(&rates).unwrap();
+        let mut proj = IntPitchingProjection::new_player("test01", 2016);
+        proj.weighted_rate_add(600, &rates, 1.0);
+        let proj = proj.finalize(&PitchingRate::ALL, Some(&constants), &BaseRuns::new(), 0.0);
+        let fip = proj.fip.unwrap();
+        assert!((fip - proj.era).abs() < 1e-4);
+        assert!((proj.xfip.unwrap() - fip).abs() < 1e-4);
+    }
 
-pub fn write_pitching_projection(projections: &Vec<PitchingProjection>, year: u16) -> Result<(), csv::Error> {
-    let output_file = format!("PitchingCapuchin{}.csv", year);
-    let output_path = Path::new(&output_file);
-    let mut wtr = csv::Writer::from_path(&output_path)?;
+    #[test]
+    fn components_regress_by_their_own_amount() {
+        let rates = BattingSeasonSummaryRates::from(league_batting());
+        let mut league = IntBattingProjection::league();
+        league.weighted_rate_add(1200, &rates, 1.0);
+        // Twice the league's strikeouts and walks over 600 PA.
+        let player = || {
+            let mut player = IntBattingProjection::new_player("test01", 2016);
+            player.weighted_rate_add(600, &rates, 1.0);
+            player.adjust(BattingStat::So, 2.0);
+            player.adjust(BattingStat::Bb, 2.0);
+            player
+        };
+
+        let mut overall = player();
+        overall.regress(&league, &BTreeMap::new());
+        let mut components = player();
+        components.regress(&league, &BTreeMap::from([(BattingStat::So, 60.0)]));
+
+        let league_so = rates.so * 1800.0;
+        // 600 PA against 1200 of the league keeps a third of the difference, 600 against 60 keeps
+        // ten elevenths of it.
+        assert!((overall.so / league_so - (1.0 + 1.0 / 3.0)).abs() < 1e-4);
+        assert!((components.so / league_so - (1.0 + 10.0 / 11.0)).abs() < 1e-4);
+        assert_eq!(components.bb, overall.bb);
+        assert_eq!(components.pa, overall.pa);
+    }
+
+    #[test]
+    fn pitching_components_regress_by_their_own_amount() {
+        let rates = PitchingSeasonSummaryRates::from(league_pitching());
+        let mut league = IntPitchingProjection::league();
+        league.weighted_rate_add(180, &rates, 1.0);
+        let player = || {
+            let mut player = IntPitchingProjection::new_player("test01", 2016);
+            player.weighted_rate_add(540, &rates, 1.0);
+            player.adjust(PitchingStat::So, 2.0);
+            player
+        };
 
-    for projection in projections {
-        let _result = wtr.serialize(projection)?;
+        let mut overall = player();
+        overall.regress(&league, &BTreeMap::new());
+        let mut components = player();
+        components.regress(&league, &BTreeMap::from([(PitchingStat::So, 1620.0)]));
+
+        let league_so = rates.so * 720.0;
+        assert!((overall.so / league_so - (1.0 + 3.0 / 4.0)).abs() < 1e-4);
+        assert!((components.so / league_so - (1.0 + 1.0 / 4.0)).abs() < 1e-4);
+        assert_eq!(components.h, overall.h);
     }
 
-    Ok(())
+    fn pitching_season(playerid: &str, ipouts: u16) -> PitchingSeason {
+        PitchingSeason {
+            playerid: playerid.to_string(),
+            yearid: 2015,
+            stint: 1,
+            teamid: "SEA".to_string(),
+            lgid: "AL".to_string(),
+            ipouts,
+            w: 0,
+            l: 0,
+            g: 10,
+            gs: 0,
+            sv: 0,
+            h: 0,
+            r: 0,
+            er: 0,
+            hr: 0,
+            so: 0,
+            bb: 0,
+            ibb: 0,
+            hbp: 0,
+            wp: 0,
+            bk: 0,
+        }
+    }
+
+    #[test]
+    fn pitching_only_season_is_pitcher() {
+        let mut player = Player::new();
+        player.add_ip(&pitching_season("test01", 30));
+        assert!(player.player_type(2015, u16::MAX, u16::MAX) == PlayerType::Pitcher);
+        assert!(player.is_pitcher(2015, u16::MAX, u16::MAX));
+        assert!(!player.is_batter(2015, u16::MAX, u16::MAX));
+        assert!(player.player_type(2014, u16::MAX, u16::MAX) == PlayerType::NoAppearances);
+    }
+
+    #[test]
+    fn translate_batting_with_default_factors() {
+        let factor = minors::Translations::new().factor("AAA").unwrap();
+        let mut season = batting_season(500, 150, 50, 100);
+        season.translate(factor);
+        assert_eq!(season.pa, 500);
+        assert_eq!(season.h, 128);
+        assert_eq!(season.bb, 43);
+        assert_eq!(season.so, 118);
+        assert_eq!(season.ab, 457);
+
+        // Rookie ball strikeouts can't outgrow the AB left after the hits.
+        let factor = minors::Translations::new().factor("RK").unwrap();
+        let mut season = batting_season(100, 20, 0, 70);
+        season.translate(factor);
+        assert_eq!(season.h, 10);
+        assert_eq!(season.ab, 100);
+        assert_eq!(season.so, 90);
+    }
+
+    #[test]
+    fn translate_pitching_with_default_factors() {
+        let factor = minors::Translations::new().factor("AA").unwrap();
+        let mut season = pitching_season("test01", 450);
+        season.h = 150;
+        season.r = 60;
+        season.er = 54;
+        season.bb = 45;
+        season.so = 120;
+        season.translate(factor);
+        assert_eq!(season.ipouts, 450);
+        assert_eq!(season.h, 200);
+        assert_eq!(season.r, 80);
+        assert_eq!(season.er, 72);
+        assert_eq!(season.bb, 60);
+        assert_eq!(season.so, 90);
+    }
 }
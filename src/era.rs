@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use crate::databank::{BattingSeasonSummaryRates, BattingStat, PitchingSeasonSummaryRates, PitchingStat};
+
+
+/// League environment that past seasons are rescaled to before they are weighted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EraTarget {
+    /// The most recent season before the projection.
+    Latest,
+    /// Each league rate's linear trend over the weighted seasons, carried to the projected year.
+    Trend,
+}
+
+impl EraTarget {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EraTarget::Latest => "latest",
+            EraTarget::Trend => "trend",
+        }
+    }
+}
+
+impl FromStr for EraTarget {
+    type Err = String;
+
+    fn from_str(target: &str) -> Result<Self, Self::Err> {
+        match target.to_lowercase().as_str() {
+            "latest" => Ok(EraTarget::Latest),
+            "trend" => Ok(EraTarget::Trend),
+            _ => Err(format!("Unknown era target '{}', expected latest or trend", target)),
+        }
+    }
+}
+
+/// For every season with league totals, the factor for each stat that moves it into the target
+/// environment.
+pub fn batting_factors(target: EraTarget, totals: &BTreeMap<u16, BattingSeasonSummaryRates>,
+                       start_year: u16, year: u16) -> BTreeMap<u16, Vec<(BattingStat, f32)>> {
+    factors(target, &BattingStat::ALL, start_year, year,
+            |season_year, stat| totals.get(&season_year).and_then(|rates| rates.rate(stat)))
+}
+
+/// For every season with league totals, the factor for each stat that moves it into the target
+/// environment.
+pub fn pitching_factors(target: EraTarget, totals: &BTreeMap<u16, PitchingSeasonSummaryRates>,
+                        start_year: u16, year: u16) -> BTreeMap<u16, Vec<(PitchingStat, f32)>> {
+    factors(target, &PitchingStat::ALL, start_year, year,
+            |season_year, stat| totals.get(&season_year).and_then(|rates| rates.rate(stat)))
+}
+
+/// The same as `batting_factors` for each league's own totals, so a season moves along with the
+/// league it was played in. Leagues missing from a season have no factors for it.
+pub fn batting_league_factors(target: EraTarget,
+                              totals: &BTreeMap<(u16, String), BattingSeasonSummaryRates>,
+                              start_year: u16, year: u16)
+    -> BTreeMap<(u16, String), Vec<(BattingStat, f32)>>
+{
+    by_league(totals, start_year, year, |lgid| {
+        factors(target, &BattingStat::ALL, start_year, year, |season_year, stat| {
+            totals.get(&(season_year, lgid.to_string())).and_then(|rates| rates.rate(stat))
+        })
+    })
+}
+
+/// The same as `pitching_factors` for each league's own totals, so a season moves along with the
+/// league it was played in. Leagues missing from a season have no factors for it.
+pub fn pitching_league_factors(target: EraTarget,
+                               totals: &BTreeMap<(u16, String), PitchingSeasonSummaryRates>,
+                               start_year: u16, year: u16)
+    -> BTreeMap<(u16, String), Vec<(PitchingStat, f32)>>
+{
+    by_league(totals, start_year, year, |lgid| {
+        factors(target, &PitchingStat::ALL, start_year, year, |season_year, stat| {
+            totals.get(&(season_year, lgid.to_string())).and_then(|rates| rates.rate(stat))
+        })
+    })
+}
+
+/// Factors for a season split between leagues, averaged by how much was played in each. Stats
+/// without a factor in a league count as unchanged there.
+pub fn blend<S: Copy + PartialEq>(shares: &[(&[(S, f32)], f32)]) -> Vec<(S, f32)> {
+    let total: f32 = shares.iter().map(|(_, share)| share).sum();
+    if total <= 0.0 {
+        return Vec::new();
+    }
+    let mut stats = Vec::new();
+    for (factors, _) in shares {
+        for (stat, _) in factors.iter() {
+            if !stats.contains(stat) {
+                stats.push(*stat);
+            }
+        }
+    }
+    stats.into_iter()
+        .map(|stat| {
+            let weighted: f32 = shares.iter()
+                .map(|(factors, share)| {
+                    let factor = factors.iter()
+                        .find(|(league_stat, _)| *league_stat == stat)
+                        .map_or(1.0, |(_, factor)| *factor);
+                    factor * share
+                })
+                .sum();
+            (stat, weighted / total)
+        })
+        .collect()
+}
+
+fn by_league<R, S, F>(totals: &BTreeMap<(u16, String), R>, start_year: u16, year: u16,
+                      league_factors: F) -> BTreeMap<(u16, String), Vec<(S, f32)>>
+    where F: Fn(&str) -> BTreeMap<u16, Vec<(S, f32)>>
+{
+    let leagues: BTreeSet<&str> = totals.keys()
+        .filter(|(season_year, _)| (start_year..year).contains(season_year))
+        .map(|(_, lgid)| lgid.as_str())
+        .collect();
+    let mut factors = BTreeMap::new();
+    for lgid in leagues {
+        for (season_year, season_factors) in league_factors(lgid) {
+            if !season_factors.is_empty() {
+                factors.insert((season_year, lgid.to_string()), season_factors);
+            }
+        }
+    }
+    factors
+}
+
+fn factors<S, F>(target: EraTarget, stats: &[S], start_year: u16, year: u16, rate: F)
+    -> BTreeMap<u16, Vec<(S, f32)>>
+    where S: Copy,
+          F: Fn(u16, S) -> Option<f32>
+{
+    let mut factors = BTreeMap::new();
+    for season_year in start_year..year {
+        let season_factors = stats.iter()
+            .filter_map(|stat| {
+                let from = rate(season_year, *stat)?;
+                let to = target_rate(target, start_year, year, |year| rate(year, *stat))?;
+                // A season without data, or a trend that runs out, leaves the stat alone.
+                let usable = |rate: f32| rate.is_finite() && rate > 0.0;
+                let factor = if usable(from) && usable(to) { to / from } else { 1.0 };
+                Some((*stat, factor))
+            })
+            .collect();
+        factors.insert(season_year, season_factors);
+    }
+    factors
+}
+
+/// The league rate in the target environment, given the rate for each season before `year`.
+/// Seasons whose rate is not a number are skipped. A trend needs at least two seasons, otherwise
+/// it is the latest season.
+pub fn target_rate<F: Fn(u16) -> Option<f32>>(target: EraTarget, start_year: u16, year: u16, rate: F)
+    -> Option<f32>
+{
+    let points: Vec<(f32, f32)> = (start_year..year)
+        .filter_map(|season_year| rate(season_year).map(|rate| (season_year as f32, rate)))
+        .filter(|(_, rate)| rate.is_finite())
+        .collect();
+    let (_, latest) = *points.last()?;
+    if target == EraTarget::Latest || points.len() < 2 {
+        return Some(latest);
+    }
+
+    let n = points.len() as f32;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f32>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f32>() / n;
+    let covariance: f32 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f32 = points.iter().map(|(x, _)| (x - mean_x) * (x - mean_x)).sum();
+    let slope = covariance / variance;
+    Some(mean_y + slope * (year as f32 - mean_x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // League rates with a season in the middle that has no data, so its rate is 0/0.
+    fn rate_with_gap(year: u16) -> Option<f32> {
+        match year {
+            2012 => Some(0.10),
+            2013 => Some(f32::NAN),
+            2014 => Some(0.12),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn target_rate_skips_gap() {
+        assert_eq!(target_rate(EraTarget::Latest, 2012, 2015, rate_with_gap), Some(0.12));
+        // The gap would be the latest season.
+        assert_eq!(target_rate(EraTarget::Latest, 2012, 2014, rate_with_gap), Some(0.10));
+        let trend = target_rate(EraTarget::Trend, 2012, 2015, rate_with_gap).unwrap();
+        assert!((trend - 0.13).abs() < 1e-5);
+        assert_eq!(target_rate(EraTarget::Trend, 2013, 2014, rate_with_gap), None);
+    }
+
+    #[test]
+    fn factors_with_gap() {
+        for target in &[EraTarget::Latest, EraTarget::Trend] {
+            let factors = factors(*target, &[BattingStat::Hr], 2012, 2016,
+                                  |year, _| rate_with_gap(year));
+            for (year, season_factors) in &factors {
+                for (_, factor) in season_factors {
+                    assert!(factor.is_finite() && *factor > 0.0, "{} {}", year, factor);
+                }
+            }
+            // Seasons without data are left alone.
+            assert_eq!(factors[&2013], vec![(BattingStat::Hr, 1.0)]);
+            assert!(factors[&2015].is_empty());
+        }
+        let factors = factors(EraTarget::Latest, &[BattingStat::Hr], 2012, 2016,
+                              |year, _| rate_with_gap(year));
+        assert!((factors[&2012][0].1 - 1.2).abs() < 1e-5);
+        assert_eq!(factors[&2014][0].1, 1.0);
+    }
+
+    #[test]
+    fn factors_with_trend_below_zero() {
+        let rate = |year: u16, _| Some(0.03 - 0.01 * (year - 2012) as f32);
+        let factors = factors(EraTarget::Trend, &[BattingStat::Triple], 2012, 2016, rate);
+        assert_eq!(factors[&2014][0].1, 1.0);
+    }
+}
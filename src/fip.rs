@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::databank::{PitchingSeasonSummaryRates, PitchingStat};
+use crate::era::{self, EraTarget};
+
+
+// Runs a home run, a walk or hit batter and a strikeout are worth in FIP, per inning.
+pub const FIP_HR: f32 = 13.0;
+pub const FIP_BB: f32 = 3.0;
+pub const FIP_SO: f32 = 2.0;
+
+
+/// League context that puts FIP and xFIP on the same scale as ERA.
+#[derive(Clone, Debug, Serialize)]
+pub struct FipConstants {
+    /// Added to FIP so the league's FIP matches its ERA.
+    pub constant: f32,
+    /// League home runs per ball put in play, including the home runs. Lahman has no batted ball
+    /// types, so this stands in for HR/FB in xFIP.
+    pub hr_per_contact: f32,
+}
+
+impl FipConstants {
+    /// Derive the constants from a season's league rates per out.
+    pub fn from_rates(rates: &PitchingSeasonSummaryRates) -> Option<Self> {
+        let rate = |stat| rates.rate(stat).unwrap_or(0.0);
+        let hr = rate(PitchingStat::Hr);
+        let so = rate(PitchingStat::So);
+        // Every out that was not a strikeout plus every hit came from a ball in play.
+        let contact = 1.0 - so + rate(PitchingStat::H);
+        if *rates.ipouts() == 0 || contact <= 0.0 {
+            return None;
+        }
+
+        let era = 27.0 * rate(PitchingStat::Er);
+        let fip = 3.0 * (FIP_HR * hr + FIP_BB * (rate(PitchingStat::Bb) + rate(PitchingStat::Hbp))
+                         - FIP_SO * so);
+        Some(FipConstants {
+            constant: era - fip,
+            hr_per_contact: hr / contact,
+        })
+    }
+
+    /// Constants for `year`, estimated from the seasons from `start_year` on the same way past
+    /// seasons are moved into a target era.
+    pub fn estimate(target: EraTarget, totals: &BTreeMap<u16, PitchingSeasonSummaryRates>,
+                    start_year: u16, year: u16) -> Option<Self> {
+        let seasons: BTreeMap<u16, FipConstants> = totals.range(start_year..year)
+            .filter_map(|(season_year, rates)| {
+                FipConstants::from_rates(rates).map(|constants| (*season_year, constants))
+            })
+            .collect();
+        let estimate = |field: fn(&FipConstants) -> f32| {
+            era::target_rate(target, start_year, year,
+                             |season_year| seasons.get(&season_year).map(field))
+        };
+        Some(FipConstants {
+            constant: estimate(|constants| constants.constant)?,
+            hr_per_contact: estimate(|constants| constants.hr_per_contact)?,
+        })
+    }
+}
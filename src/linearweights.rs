@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::databank::{BattingSeasonSummaryRates, BattingStat};
+use crate::era::{self, EraTarget};
+
+
+// Runs each event is worth above the league's runs per out, with a home run at a flat 1.4. These
+// stand in for a run expectancy table so the weights only need season totals.
+const RUN_BB: f32 = 0.14;
+const RUN_HBP: f32 = 0.025;
+const RUN_SINGLE: f32 = 0.155;
+const RUN_DOUBLE: f32 = 0.3;
+const RUN_TRIPLE: f32 = 0.27;
+const RUN_HR: f32 = 1.4;
+const RUN_SB: f32 = 0.2;
+const RUN_CS: f32 = 0.075;
+
+
+/// wOBA weights and the league context needed to turn wOBA into runs.
+#[derive(Clone, Debug, Serialize)]
+pub struct LinearWeights {
+    /// League wOBA, which the scale sets equal to the league OBP.
+    pub woba: f32,
+    /// Divide a wOBA difference by this to get runs per PA.
+    pub scale: f32,
+    pub runs_per_pa: f32,
+    pub bb: f32,
+    pub hbp: f32,
+    pub single: f32,
+    pub double: f32,
+    pub triple: f32,
+    pub hr: f32,
+}
+
+impl LinearWeights {
+    /// Derive the weights from a season's league rates per PA. There are none for a season
+    /// without any PA.
+    pub fn from_rates(rates: &BattingSeasonSummaryRates) -> Option<Self> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        let rate = |stat| rates.rate(stat).unwrap_or(0.0);
+        let h = rate(BattingStat::H);
+        let double = rate(BattingStat::Double);
+        let triple = rate(BattingStat::Triple);
+        let hr = rate(BattingStat::Hr);
+        let single = h - double - triple - hr;
+        let bb = rate(BattingStat::Bb);
+        let unintentional_bb = bb - rate(BattingStat::Ibb);
+        let hbp = rate(BattingStat::Hbp);
+        let sf = rate(BattingStat::Sf);
+        let ab = 1.0 - (bb + hbp + sf + rate(BattingStat::Sh));
+        let outs = ab - h + sf + rate(BattingStat::Sh) + rate(BattingStat::Cs)
+            + rate(BattingStat::Gidp);
+        if !positive(outs) || !positive(ab - h + sf) {
+            return None;
+        }
+
+        let runs_per_out = rate(BattingStat::R) / outs;
+        let run_bb = runs_per_out + RUN_BB;
+        let run_hbp = run_bb + RUN_HBP;
+        let run_single = run_bb + RUN_SINGLE;
+        let run_double = run_single + RUN_DOUBLE;
+        let run_triple = run_double + RUN_TRIPLE;
+        let run_cs = 2.0 * runs_per_out + RUN_CS;
+        let runs = run_bb * unintentional_bb + run_hbp * hbp + run_single * single
+            + run_double * double + run_triple * triple + RUN_HR * hr
+            + RUN_SB * rate(BattingStat::Sb) - run_cs * rate(BattingStat::Cs);
+        // What an out costs, so that the league's events and outs balance.
+        let run_out = runs / (ab - h + sf);
+
+        let raw_woba = ((run_bb + run_out) * unintentional_bb + (run_hbp + run_out) * hbp
+                        + (run_single + run_out) * single + (run_double + run_out) * double
+                        + (run_triple + run_out) * triple + (RUN_HR + run_out) * hr)
+            / (ab + unintentional_bb + sf + hbp);
+        let obp = (h + bb + hbp) / (ab + bb + hbp + sf);
+        if !positive(raw_woba) {
+            return None;
+        }
+        let scale = obp / raw_woba;
+
+        Some(LinearWeights {
+            woba: obp,
+            scale,
+            runs_per_pa: rate(BattingStat::R),
+            bb: (run_bb + run_out) * scale,
+            hbp: (run_hbp + run_out) * scale,
+            single: (run_single + run_out) * scale,
+            double: (run_double + run_out) * scale,
+            triple: (run_triple + run_out) * scale,
+            hr: (RUN_HR + run_out) * scale,
+        })
+    }
+
+    /// Weights for `year`, estimated from the seasons from `start_year` on the same way past
+    /// seasons are moved into a target era.
+    pub fn estimate(target: EraTarget, totals: &BTreeMap<u16, BattingSeasonSummaryRates>,
+                    start_year: u16, year: u16) -> Option<Self> {
+        let seasons: BTreeMap<u16, LinearWeights> = totals.range(start_year..year)
+            .filter_map(|(season_year, rates)| {
+                LinearWeights::from_rates(rates).map(|weights| (*season_year, weights))
+            })
+            .collect();
+        let estimate = |field: fn(&LinearWeights) -> f32| {
+            era::target_rate(target, start_year, year, |season_year| {
+                seasons.get(&season_year).map(field).filter(|value| value.is_finite())
+            })
+        };
+        Some(LinearWeights {
+            woba: estimate(|weights| weights.woba)?,
+            scale: estimate(|weights| weights.scale)?,
+            runs_per_pa: estimate(|weights| weights.runs_per_pa)?,
+            bb: estimate(|weights| weights.bb)?,
+            hbp: estimate(|weights| weights.hbp)?,
+            single: estimate(|weights| weights.single)?,
+            double: estimate(|weights| weights.double)?,
+            triple: estimate(|weights| weights.triple)?,
+            hr: estimate(|weights| weights.hr)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::databank::BattingSeasonSummary;
+    use crate::databank::tests::league_batting;
+
+    #[test]
+    fn no_weights_without_pa() {
+        let rates = BattingSeasonSummaryRates::from(BattingSeasonSummary::new());
+        assert!(LinearWeights::from_rates(&rates).is_none());
+    }
+
+    #[test]
+    fn estimate_skips_empty_seasons() {
+        let totals = BTreeMap::from([
+            (2014, BattingSeasonSummaryRates::from(league_batting())),
+            (2015, BattingSeasonSummaryRates::from(BattingSeasonSummary::new())),
+        ]);
+        let season = LinearWeights::from_rates(&totals[&2014]).unwrap();
+        for target in &[EraTarget::Latest, EraTarget::Trend] {
+            let weights = LinearWeights::estimate(*target, &totals, 2014, 2016).unwrap();
+            assert_eq!(weights.woba, season.woba);
+            assert_eq!(weights.hr, season.hr);
+        }
+        assert!(LinearWeights::estimate(EraTarget::Latest, &totals, 2015, 2016).is_none());
+    }
+}
@@ -1,35 +1,135 @@
 #![recursion_limit = "1024"]
 
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::path::Path;
 use std::str::FromStr;
 use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use clap::{Arg, App};
+use clap::{App, AppSettings, Arg, SubCommand};
 
+use crate::databank::{BattingRate, BattingStat, PitchingRate, PitchingStat};
 
+
+mod aging;
+mod backtest;
+mod baseruns;
+mod compare;
 mod databank;
+mod era;
+mod fip;
+mod linearweights;
+mod metrics;
+mod minors;
+mod output;
+mod park;
+mod playingtime;
 mod projection;
 mod register;
+mod roster;
+mod simulate;
+mod tune;
+mod uncertainty;
 
 
 const PEAK_AGE: u8 = 27;
 const BATTER_REGRESS: u16 = 1200;
 const STARTER_REGRESS: u16 = 60;
 const RELIEVER_REGRESS: u16 = 25;
-const BATTER_WEIGHTS: &'static [f32] = &[5.0, 4.0, 3.0];
-const PITCHER_WEIGHTS: &'static [f32] = &[3.0, 2.0, 1.0];
+// With two-way players turned on, a season with at least this many PA and IP makes a two-way
+// player.
+const TWO_WAY_PA: u16 = 100;
+const TWO_WAY_IP: u16 = 20;
+// Roughly the number of PA (IP for pitchers) needed for each component to be half signal and half
+// noise, from Russell Carleton's stabilization research. These are only used when asked for with
+// "stabilization", and components not listed regress by the overall amount above.
+const BATTER_COMPONENT_REGRESS: &[(BattingStat, u16)] = &[
+    (BattingStat::So, 60),
+    (BattingStat::Bb, 120),
+    (BattingStat::Hr, 170),
+    (BattingStat::Hbp, 240),
+    (BattingStat::H, 910),
+    (BattingStat::Double, 1610),
+    (BattingStat::Triple, 1610),
+];
+const PITCHER_COMPONENT_REGRESS: &[(PitchingStat, u16)] = &[
+    (PitchingStat::So, 16),
+    (PitchingStat::Bb, 40),
+    (PitchingStat::Hbp, 150),
+    (PitchingStat::Hr, 300),
+    (PitchingStat::H, 650),
+];
+const BATTER_WEIGHTS: &[f32] = &[5.0, 4.0, 3.0];
+const PITCHER_WEIGHTS: &[f32] = &[3.0, 2.0, 1.0];
+// A minor league season counts this much compared to an MLB season from the same year.
+const MINORS_WEIGHT: f32 = 0.5;
+// ERA is half projected earned runs and half Base Runs.
+const ERA_BLEND: f32 = 0.5;
+// Number of seasons to weight when the weights come from a half-life.
+const HISTORY: usize = 3;
+// Seasons simulated for each player when not given.
+const SIMULATIONS: u32 = 1000;
 
 
 fn main() {
     let app = App::new("Capuchin")
         .version("0.1.0")
         .about("Simple baseball projections")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(Arg::with_name("minor_batting")
+             .long("minor-batting")
+             .value_name("FILE")
+             .help("Minor league batting in the Batting.csv layout with a level column")
+             .takes_value(true))
+        .arg(Arg::with_name("minor_pitching")
+             .long("minor-pitching")
+             .value_name("FILE")
+             .help("Minor league pitching in the Pitching.csv layout with a level column")
+             .takes_value(true))
+        .arg(Arg::with_name("minor_factors")
+             .long("minor-factors")
+             .value_name("LEVEL=FACTOR,...")
+             .help("Translation factors for minor league levels, defaults to AAA=0.85,AA=0.75,A+=0.65,A=0.6,A-=0.55,Rk=0.5")
+             .takes_value(true))
+        .arg(Arg::with_name("minors_weight")
+             .long("minors-weight")
+             .value_name("WEIGHT")
+             .help("Weight of a minor league season relative to an MLB season, defaults to 0.5")
+             .takes_value(true))
+        .arg(Arg::with_name("park_factors")
+             .long("park-factors")
+             .value_name("FILE")
+             .help("CSV of teamID, yearID and R, H, 2B, 3B, HR, SO and BB park factors to neutralize seasons with")
+             .takes_value(true))
+        .arg(Arg::with_name("reapply_park")
+             .long("reapply-park")
+             .requires("park_factors")
+             .help("Put projections back into the park of the player's latest team"))
         .arg(Arg::with_name("register")
              .short("r")
              .long("register")
              .value_name("FILE")
-             .help("people.csv file")
+             .help("Chadwick people.csv or Lahman People.csv file")
+             .takes_value(true))
+        .arg(Arg::with_name("register_format")
+             .long("register-format")
+             .value_name("FORMAT")
+             .possible_values(&["chadwick", "lahman"])
+             .help("Format of the register, detected from its header when not given")
+             .takes_value(true))
+        .arg(Arg::with_name("player_info")
+             .long("player-info")
+             .help("Add names, handedness, debut and MLBAM, Retrosheet and FanGraphs IDs from the register to the projections"))
+        .arg(Arg::with_name("batting_rates")
+             .long("batting-rates")
+             .value_name("RATES")
+             .help("Rate stats to add to batting projections, from avg, obp, slg, ops, iso, babip, k_pct, bb_pct, woba, wraa and wrc_plus, or all. Defaults to none")
+             .takes_value(true))
+        .arg(Arg::with_name("pitching_rates")
+             .long("pitching-rates")
+             .value_name("RATES")
+             .help("Rate stats to add to pitching projections, from fip, xfip, whip, k_9, bb_9, hr_9, k_pct and bb_pct, or all. Defaults to none")
              .takes_value(true))
         .arg(Arg::with_name("batting")
              .short("b")
@@ -58,12 +158,98 @@ fn main() {
              .value_name("IP")
              .help("Number of league average IP to regress relievers")
              .takes_value(true))
+        .arg(Arg::with_name("batter_components")
+             .long("batter-components")
+             .value_name("STAT=PA,...")
+             .help("PA to regress individual batting components, with stabilization for the stabilization point of each. Defaults to --batter-regress for all")
+             .takes_value(true))
+        .arg(Arg::with_name("pitcher_components")
+             .long("pitcher-components")
+             .value_name("STAT=IP,...")
+             .help("IP to regress individual pitching components, with stabilization for the stabilization point of each. Defaults to the starter or reliever amount for all")
+             .takes_value(true))
+        .arg(Arg::with_name("normalize_era")
+             .long("normalize-era")
+             .value_name("TARGET")
+             .possible_values(&["latest", "trend"])
+             .help("Rescale past seasons to the league environment of the latest season or its trend to the projected year")
+             .takes_value(true))
+        .arg(Arg::with_name("mlb_baseline")
+             .long("mlb-baseline")
+             .help("Regress toward MLB-wide means instead of each player's league means"))
+        .arg(Arg::with_name("era_blend")
+             .long("era-blend")
+             .value_name("SHARE")
+             .help("Share of ERA from Base Runs, from 0 to 1, with the rest from projected earned runs. Defaults to 0.5")
+             .takes_value(true))
+        .arg(Arg::with_name("baseruns")
+             .long("baseruns")
+             .value_name("NAME=VALUE,...")
+             .help("Base Runs B factor coefficients, defaults to tb=1.4,h_tb=1.12,hr_tb=4,h=0.6,hr=3,bb=0.1,multiplier=1.1")
+             .takes_value(true))
+        .arg(Arg::with_name("calibrate_baseruns")
+             .long("calibrate-baseruns")
+             .help("Set the Base Runs multiplier each season so league Base Runs match league earned runs"))
         .arg(Arg::with_name("peak_age")
              .short("a")
              .long("peak-age")
              .value_name("AGE")
              .help("Peak age for player")
              .takes_value(true))
+        .arg(Arg::with_name("age_convention")
+             .long("age-convention")
+             .value_name("CONVENTION")
+             .possible_values(&["end-of-year", "mlb", "bbref", "exact"])
+             .help("How to calculate season age: end-of-year (default), mlb (June 30), bbref (July 1) or exact")
+             .takes_value(true))
+        .arg(Arg::with_name("aging")
+             .long("aging")
+             .value_name("MODEL")
+             .help("Aging curves: marcel (default), components or a CSV file of type,stat,young,old")
+             .takes_value(true))
+        .arg(Arg::with_name("playing_time_weights")
+             .long("playing-time-weights")
+             .value_name("C1,C2,...")
+             .help("Share of each previous season's PA or IP to project, defaults to 0.5,0.1")
+             .takes_value(true))
+        .arg(Arg::with_name("batter_baseline")
+             .long("batter-baseline")
+             .value_name("PA")
+             .help("PA every batter gets before adding previous seasons, defaults to 200")
+             .takes_value(true))
+        .arg(Arg::with_name("starter_baseline")
+             .long("starter-baseline")
+             .value_name("IP")
+             .help("IP every starter gets before adding previous seasons, defaults to 60")
+             .takes_value(true))
+        .arg(Arg::with_name("reliever_baseline")
+             .long("reliever-baseline")
+             .value_name("IP")
+             .help("IP every reliever gets before adding previous seasons, defaults to 25")
+             .takes_value(true))
+        .arg(Arg::with_name("depth_chart")
+             .long("depth-chart")
+             .value_name("FILE")
+             .help("CSV of playerID with PA, IP and GS to use instead of the playing time formula")
+             .takes_value(true))
+        .arg(Arg::with_name("roster")
+             .long("roster")
+             .value_name("FILE")
+             .help("CSV of playerID with optional age, role, teamID and lgID; players without MLB history get a replacement level projection")
+             .takes_value(true))
+        .arg(Arg::with_name("two_way")
+             .long("two-way")
+             .help("Keep both projections for two-way players and split their playing time"))
+        .arg(Arg::with_name("two_way_pa")
+             .long("two-way-pa")
+             .value_name("PA")
+             .help("PA in a season, along with --two-way-ip, to count as a two-way player; implies --two-way")
+             .takes_value(true))
+        .arg(Arg::with_name("two_way_ip")
+             .long("two-way-ip")
+             .value_name("IP")
+             .help("IP in a season, along with --two-way-pa, to count as a two-way player; implies --two-way")
+             .takes_value(true))
         .arg(Arg::with_name("batter_weights")
              .short("w")
              .long("batter-weights")
@@ -76,54 +262,264 @@ fn main() {
              .value_name("W1,W2,...")
              .help("Weights to use for pitchers in previous seasons")
              .takes_value(true))
+        .arg(Arg::with_name("batter_half_life")
+             .long("batter-half-life")
+             .value_name("YEARS")
+             .help("Weight batter seasons by exponential decay with this half-life instead of --batter-weights")
+             .conflicts_with("batter_weights")
+             .takes_value(true))
+        .arg(Arg::with_name("pitcher_half_life")
+             .long("pitcher-half-life")
+             .value_name("YEARS")
+             .help("Weight pitcher seasons by exponential decay with this half-life instead of --pitcher-weights")
+             .conflicts_with("pitcher_weights")
+             .takes_value(true))
+        .arg(Arg::with_name("history")
+             .long("history")
+             .value_name("SEASONS")
+             .help("Number of previous seasons to weight with a half-life, defaults to 3")
+             .takes_value(true))
+        .arg(Arg::with_name("format")
+             .long("format")
+             .value_name("FORMAT")
+             .possible_values(&["csv", "json", "jsonl"])
+             .help("Format of the projections. JSON is an object with the metadata and an array of projections, JSON Lines has the metadata on the first line. Defaults to csv")
+             .takes_value(true))
+        .arg(Arg::with_name("percentiles")
+             .long("percentiles")
+             .help("Also write 10th, 50th and 90th percentiles of key stats for each player"))
         .arg(Arg::with_name("year")
              .value_name("YEAR")
              .required(true)
              .multiple(true)
              .help("Year(s) to project")
              .takes_value(true))
+        .subcommand(SubCommand::with_name("backtest")
+             .about("Project past seasons and score them against what happened")
+             .arg(Arg::with_name("start_year")
+                  .value_name("START")
+                  .required(true)
+                  .help("First year to project")
+                  .takes_value(true))
+             .arg(Arg::with_name("end_year")
+                  .value_name("END")
+                  .help("Last year to project, defaults to START")
+                  .takes_value(true)))
+        .subcommand(SubCommand::with_name("tune")
+             .about("Search for the parameters that best project past seasons")
+             .arg(Arg::with_name("start_year")
+                  .value_name("START")
+                  .required(true)
+                  .help("First year to project")
+                  .takes_value(true))
+             .arg(Arg::with_name("end_year")
+                  .value_name("END")
+                  .help("Last year to project, defaults to START")
+                  .takes_value(true))
+             .arg(Arg::with_name("objective")
+                  .long("objective")
+                  .value_name("OBJECTIVE")
+                  .possible_values(&["woba", "era"])
+                  .help("What to minimize: woba (PA-weighted wOBA RMSE) or era (IP-weighted ERA RMSE)")
+                  .takes_value(true)))
+        .subcommand(SubCommand::with_name("simulate")
+             .about("Simulate seasons from the projections and summarize each player's outcomes")
+             .arg(Arg::with_name("year")
+                  .value_name("YEAR")
+                  .required(true)
+                  .help("Year to project and simulate")
+                  .takes_value(true))
+             .arg(Arg::with_name("simulations")
+                  .short("n")
+                  .long("simulations")
+                  .value_name("N")
+                  .help("Number of seasons to simulate for each player, defaults to 1000")
+                  .takes_value(true))
+             .arg(Arg::with_name("seed")
+                  .long("seed")
+                  .value_name("SEED")
+                  .help("Seed for the random draws, defaults to one from the clock")
+                  .takes_value(true)))
+        .subcommand(SubCommand::with_name("compare")
+             .about("Compare two projection files, such as Marcels and Capuchin")
+             .arg(Arg::with_name("reference")
+                  .value_name("REFERENCE")
+                  .required(true)
+                  .help("Reference projection file")
+                  .takes_value(true))
+             .arg(Arg::with_name("other")
+                  .value_name("OTHER")
+                  .required(true)
+                  .help("Projection file to compare against the reference")
+                  .takes_value(true))
+             .arg(Arg::with_name("reference_id")
+                  .long("reference-id")
+                  .value_name("COLUMN")
+                  .help("Player ID column in the reference file, defaults to playerid")
+                  .takes_value(true))
+             .arg(Arg::with_name("other_id")
+                  .long("other-id")
+                  .value_name("COLUMN")
+                  .help("Player ID column in the other file, defaults to playerid")
+                  .takes_value(true))
+             .arg(Arg::with_name("alias")
+                  .long("alias")
+                  .value_name("NAME=STAT[/DIVISOR]")
+                  .help("Treat column NAME as STAT, e.g. doubles=2B or ipouts=ip/3")
+                  .multiple(true)
+                  .number_of_values(1)
+                  .takes_value(true)))
         ;
     let matches = app.get_matches();
 
+    // Comparing projections does not need any of the data files.
+    if let Some(compare_matches) = matches.subcommand_matches("compare") {
+        run_compare(compare_matches);
+        return;
+    }
+
     let peak_age = matches.value_of("peak_age")
         .map_or(PEAK_AGE, |age| u8::from_str(age)
                                 .expect("Unable to parse peak age."));
 
+    let age_convention = matches.value_of("age_convention")
+        .map_or(register::AgeConvention::EndOfYear, |convention| {
+            register::AgeConvention::from_str(convention).expect("Unknown age convention.")
+        });
+
+    let aging = matches.value_of("aging")
+        .map_or(Ok(aging::AgingModel::marcel()), aging::AgingModel::from_name)
+        .expect("Unable to load aging curves.");
+
     let batter_regress = matches.value_of("batter_regress")
         .map_or(BATTER_REGRESS, |pa| u16::from_str(pa)
                                 .expect("Unable to parse amount to regress batters."));
 
     let starter_regress = matches.value_of("starter_regress")
-        .map_or(STARTER_REGRESS, |ip| u16::from_str(ip)
+        .map_or(STARTER_REGRESS, |ip| u16::from_str(ip).ok()
+                                .filter(|ip| *ip <= projection::MAX_PITCHER_REGRESS)
                                 .expect("Unable to parse amount to regress starters."));
     let reliever_regress = matches.value_of("reliever_regress")
-        .map_or(RELIEVER_REGRESS, |ip| u16::from_str(ip)
+        .map_or(RELIEVER_REGRESS, |ip| u16::from_str(ip).ok()
+                                .filter(|ip| *ip <= projection::MAX_PITCHER_REGRESS)
                                 .expect("Unable to parse amount to regress relievers."));
 
+    let batter_components = split_components(BATTER_COMPONENT_REGRESS,
+                                             matches.value_of("batter_components"))
+        .expect("Unable to parse batter component regression.");
+    let pitcher_components = split_components(PITCHER_COMPONENT_REGRESS,
+                                              matches.value_of("pitcher_components"))
+        .expect("Unable to parse pitcher component regression.");
+
+    let history = matches.value_of("history")
+        .map_or(HISTORY, |seasons| usize::from_str(seasons)
+                                   .expect("Unable to parse number of seasons of history."));
+
     let default_weights = Vec::from(BATTER_WEIGHTS);
-    let batter_weights = matches.value_of("batter_weights")
-        .map_or(default_weights, |weights| split_weights(weights)
-                                 .expect("Unable to parse batter weights."));
+    let batter_weights = match matches.value_of("batter_half_life") {
+        Some(half_life) => {
+            let half_life = f32::from_str(half_life).expect("Unable to parse batter half-life.");
+            half_life_weights(BATTER_WEIGHTS[0], half_life, history)
+        }
+        None => matches.value_of("batter_weights")
+            .map_or(default_weights, |weights| split_weights(weights)
+                                     .expect("Unable to parse batter weights.")),
+    };
 
     let default_weights = Vec::from(PITCHER_WEIGHTS);
-    let pitcher_weights = matches.value_of("pitcher_weights")
-        .map_or(default_weights, |weights| split_weights(weights)
-                                 .expect("Unable to parse pitcher weights."));
+    let pitcher_weights = match matches.value_of("pitcher_half_life") {
+        Some(half_life) => {
+            let half_life = f32::from_str(half_life).expect("Unable to parse pitcher half-life.");
+            half_life_weights(PITCHER_WEIGHTS[0], half_life, history)
+        }
+        None => matches.value_of("pitcher_weights")
+            .map_or(default_weights, |weights| split_weights(weights)
+                                     .expect("Unable to parse pitcher weights.")),
+    };
 
-    let years: Vec<u16> = matches.values_of("year")
-        .expect("Need a year to project.")
-        .map(|year| u16::from_str(year).expect("Expected to get integer year"))
-        .collect();
+    let mut playing_time = playingtime::PlayingTime::marcel();
+    if let Some(coefficients) = matches.value_of("playing_time_weights") {
+        playing_time.coefficients = split_weights(coefficients)
+            .expect("Unable to parse playing time weights.");
+    }
+    if let Some(pa) = matches.value_of("batter_baseline") {
+        playing_time.batter_baseline = f32::from_str(pa)
+            .expect("Unable to parse batter baseline.");
+    }
+    if let Some(ip) = matches.value_of("starter_baseline") {
+        playing_time.starter_baseline = f32::from_str(ip)
+            .expect("Unable to parse starter baseline.");
+    }
+    if let Some(ip) = matches.value_of("reliever_baseline") {
+        playing_time.reliever_baseline = f32::from_str(ip)
+            .expect("Unable to parse reliever baseline.");
+    }
+
+    let minors_weight = matches.value_of("minors_weight")
+        .map_or(MINORS_WEIGHT, |weight| f32::from_str(weight)
+                                       .expect("Unable to parse minor league weight."));
+
+    let era_blend = matches.value_of("era_blend")
+        .map_or(ERA_BLEND, |share| f32::from_str(share).ok()
+                                   .filter(|share| (0.0..=1.0).contains(share))
+                                   .expect("Unable to parse ERA blend."));
+    let mut baseruns = baseruns::BaseRuns::new();
+    if let Some(coefficients) = matches.value_of("baseruns") {
+        baseruns.set(coefficients).expect("Unable to parse Base Runs coefficients.");
+    }
+
+    let era = matches.value_of("normalize_era")
+        .map(|target| era::EraTarget::from_str(target).expect("Unknown era target."));
 
-    let mut capuchin = projection::Capuchin::new(
-        peak_age, batter_regress, starter_regress, reliever_regress, batter_weights,
-        pitcher_weights
-    );
+    let parameters = projection::Parameters {
+        peak_age,
+        age_convention,
+        aging,
+        batter_regress,
+        starter_regress,
+        reliever_regress,
+        batter_components,
+        pitcher_components,
+        batter_weights,
+        pitcher_weights,
+        era,
+        minors_weight,
+        playing_time,
+        reapply_park: matches.is_present("reapply_park"),
+        mlb_baseline: matches.is_present("mlb_baseline"),
+        era_blend,
+        baseruns,
+        calibrate_baseruns: matches.is_present("calibrate_baseruns"),
+    };
+    let mut capuchin = projection::Capuchin::new(parameters);
+
+    capuchin.include_player_info(matches.is_present("player_info"));
+    if let Some(rates) = matches.value_of("batting_rates") {
+        match split_rates(rates, &BattingRate::ALL) {
+            Ok(rates) => capuchin.include_batting_rates(rates),
+            Err(err) => {
+                println!("{}", err);
+                exit(1);
+            }
+        }
+    }
+    if let Some(rates) = matches.value_of("pitching_rates") {
+        match split_rates(rates, &PitchingRate::ALL) {
+            Ok(rates) => capuchin.include_pitching_rates(rates),
+            Err(err) => {
+                println!("{}", err);
+                exit(1);
+            }
+        }
+    }
 
     // Is the register available? Load it.
     if let Some(register_path) = matches.value_of("register") {
         let register_file = File::open(register_path).expect("Unable to open register file");
-        match register::People::from_register(register_file) {
+        let register_format = matches.value_of("register_format")
+            .map(|format| register::RegisterFormat::from_str(format)
+                          .expect("Unknown register format."));
+        match register::People::from_register(register_file, register_format) {
             Ok(people) => {
                 capuchin.load_register(people);
             }
@@ -133,10 +529,28 @@ fn main() {
         }
     }
 
+    if let Some(depth_chart) = matches.value_of("depth_chart") {
+        let depth_chart = playingtime::DepthChart::from_path(Path::new(depth_chart))
+            .expect("Unable to load depth chart.");
+        capuchin.load_depth_chart(depth_chart);
+    }
+
+    if let Some(park_factors) = matches.value_of("park_factors") {
+        let park_factors = park::ParkFactors::from_path(Path::new(park_factors))
+            .expect("Unable to load park factors.");
+        capuchin.load_park_factors(park_factors);
+    }
+
+    if let Some(roster) = matches.value_of("roster") {
+        let roster = roster::Roster::from_path(Path::new(roster))
+            .expect("Unable to load roster.");
+        capuchin.load_roster(roster);
+    }
+
     let mut loaded_batting = false;
     if let Some(batting_csv) = matches.value_of("batting") {
         let batting_csv = Path::new(batting_csv);
-        capuchin.load_batting(&batting_csv).expect("Failed load Batting.csv");
+        capuchin.load_batting(batting_csv).expect("Failed load Batting.csv");
         loaded_batting = true;
     }
     else {
@@ -146,13 +560,37 @@ fn main() {
     let mut loaded_pitching = false;
     if let Some(pitching_csv) = matches.value_of("pitching") {
         let pitching_csv = Path::new(pitching_csv);
-        capuchin.load_pitching(&pitching_csv).expect("Failed load Pitching.csv");
+        capuchin.load_pitching(pitching_csv).expect("Failed load Pitching.csv");
         loaded_pitching = true;
     }
     else {
         println!("No Pitching.csv, skipping pitcher projections.");
     }
 
+    let mut translations = minors::Translations::new();
+    if let Some(factors) = matches.value_of("minor_factors") {
+        translations.add(factors).expect("Unable to parse minor league factors.");
+    }
+    if let Some(batting_csv) = matches.value_of("minor_batting") {
+        capuchin.load_minor_batting(Path::new(batting_csv), &translations)
+            .expect("Failed to load minor league batting");
+    }
+    if let Some(pitching_csv) = matches.value_of("minor_pitching") {
+        capuchin.load_minor_pitching(Path::new(pitching_csv), &translations)
+            .expect("Failed to load minor league pitching");
+    }
+
+    if matches.is_present("two_way") || matches.is_present("two_way_pa")
+        || matches.is_present("two_way_ip") {
+        let two_way_pa = matches.value_of("two_way_pa")
+            .map_or(TWO_WAY_PA, |pa| u16::from_str(pa)
+                                  .expect("Unable to parse two-way PA."));
+        let two_way_ip = matches.value_of("two_way_ip")
+            .map_or(TWO_WAY_IP, |ip| u16::from_str(ip)
+                                  .expect("Unable to parse two-way IP."));
+        capuchin.set_two_way_thresholds(two_way_pa, two_way_ip);
+    }
+
     if loaded_batting && loaded_pitching {
         capuchin.remove_out_of_position_players();
     }
@@ -161,25 +599,217 @@ fn main() {
         exit(1);
     }
 
+    if let Some(backtest_matches) = matches.subcommand_matches("backtest") {
+        // Each year is scored against the league the season before, so it cannot be year 0.
+        let start_year = backtest_matches.value_of("start_year")
+            .map(|year| u16::from_str(year).ok().filter(|year| *year > 0)
+                        .expect("Expected to get integer start year after 0"))
+            .expect("Need a year to start the backtest.");
+        let end_year = backtest_matches.value_of("end_year")
+            .map_or(start_year, |year| u16::from_str(year)
+                                       .expect("Expected to get integer end year"));
+
+        if loaded_batting {
+            backtest::batting_backtest(&mut capuchin, start_year, end_year).print();
+        }
+        if loaded_pitching {
+            backtest::pitching_backtest(&mut capuchin, start_year, end_year).print();
+        }
+        return;
+    }
+
+    if let Some(tune_matches) = matches.subcommand_matches("tune") {
+        let start_year = tune_matches.value_of("start_year")
+            .map(|year| u16::from_str(year).expect("Expected to get integer start year"))
+            .expect("Need a year to start tuning.");
+        let end_year = tune_matches.value_of("end_year")
+            .map_or(start_year, |year| u16::from_str(year)
+                                       .expect("Expected to get integer end year"));
+        let default_objective = if loaded_batting { "woba" } else { "era" };
+        let objective = tune_matches.value_of("objective").unwrap_or(default_objective);
+        let objective = tune::Objective::from_str(objective).expect("Unknown objective.");
+        if let tune::Objective::Woba = objective {
+            if !loaded_batting {
+                println!("Tuning for wOBA needs Batting.csv.");
+                exit(1);
+            }
+        }
+        else if !loaded_pitching {
+            println!("Tuning for ERA needs Pitching.csv.");
+            exit(1);
+        }
+
+        let tuning = tune::tune(&mut capuchin, objective, start_year, end_year);
+        println!("Best {} over {}-{} after {} evaluations: {:.6}", objective, start_year,
+                 end_year, tuning.evaluations, tuning.score);
+        println!("{}", objective.describe(&tuning.parameters));
+        return;
+    }
+
+    if let Some(simulate_matches) = matches.subcommand_matches("simulate") {
+        let year = simulate_matches.value_of("year")
+            .map(|year| u16::from_str(year).expect("Expected to get integer year"))
+            .expect("Need a year to simulate.");
+        let simulations = simulate_matches.value_of("simulations")
+            .map_or(SIMULATIONS, |simulations| u32::from_str(simulations)
+                                               .expect("Unable to parse number of simulations."));
+        let seed = simulate_matches.value_of("seed")
+            .map_or_else(clock_seed, |seed| u64::from_str(seed)
+                                            .expect("Unable to parse seed."));
+        println!("Simulating {} seasons of {} with seed {}.", simulations, year, seed);
+
+        if loaded_batting {
+            let (b_projections, _) = capuchin.batting_projection(year);
+            let simulated = simulate::simulate_batting(&b_projections, year, simulations, seed);
+            let output_file = format!("BattingSimulation{}.csv", year);
+            if let Err(e) = uncertainty::write_percentiles(&simulated, &output_file) {
+                println!("Unable to write batting simulation for year {}: {}", year, e);
+            }
+        }
+        if loaded_pitching {
+            let (p_projections, _) = capuchin.pitching_projection(year);
+            let simulated = simulate::simulate_pitching(&p_projections, year, simulations, seed);
+            let output_file = format!("PitchingSimulation{}.csv", year);
+            if let Err(e) = uncertainty::write_percentiles(&simulated, &output_file) {
+                println!("Unable to write pitching simulation for year {}: {}", year, e);
+            }
+        }
+        return;
+    }
+
+    let years: Vec<u16> = matches.values_of("year")
+        .expect("Need a year to project.")
+        .map(|year| u16::from_str(year).expect("Expected to get integer year"))
+        .collect();
+    let write_percentiles = matches.is_present("percentiles");
+    let format = matches.value_of("format")
+        .map_or(output::OutputFormat::Csv, |format| output::OutputFormat::from_str(format)
+                                                    .expect("Unknown output format."));
+
     for year in &years {
         if loaded_batting {
-            let b_projections = capuchin.batting_projection(*year);
-            if let Err(e) = databank::write_batting_projection(&b_projections, *year) {
+            let (b_projections, context) = capuchin.batting_projection(*year);
+            let metadata = output::Metadata::batting(*year, capuchin.batting_history(*year),
+                                                     capuchin.parameters(), &context);
+            if let Err(e) = databank::write_batting_projection(&b_projections, *year, format,
+                                                               &metadata) {
                 println!("Unable to write batting projection for year {}: {}", year, e);
             }
+            if write_percentiles {
+                let percentiles = uncertainty::batting_percentiles(&b_projections, *year);
+                if let Err(e) = uncertainty::write_batting_percentiles(&percentiles, *year) {
+                    println!("Unable to write batting percentiles for year {}: {}", year, e);
+                }
+            }
         }
 
         if loaded_pitching {
-            let p_projections = capuchin.pitching_projection(*year);
-            if let Err(e) = databank::write_pitching_projection(&p_projections, *year) {
+            let (p_projections, context) = capuchin.pitching_projection(*year);
+            let metadata = output::Metadata::pitching(*year, capuchin.pitching_history(*year),
+                                                      capuchin.parameters(), &context);
+            if let Err(e) = databank::write_pitching_projection(&p_projections, *year, format,
+                                                                &metadata) {
                 println!("Unable to write pitching projection for year {}: {}", year, e);
             }
+            if write_percentiles {
+                let percentiles = uncertainty::pitching_percentiles(&p_projections, *year);
+                if let Err(e) = uncertainty::write_pitching_percentiles(&percentiles, *year) {
+                    println!("Unable to write pitching percentiles for year {}: {}", year, e);
+                }
+            }
         }
     }
 }
 
+fn run_compare(matches: &clap::ArgMatches) {
+    let mut aliases = compare::Aliases::new();
+    for alias in matches.values_of("alias").into_iter().flatten() {
+        if let Err(err) = aliases.add(alias) {
+            println!("{}", err);
+            exit(1);
+        }
+    }
+
+    let reference_file = matches.value_of("reference").expect("Need a reference projection.");
+    let other_file = matches.value_of("other").expect("Need a projection to compare.");
+    let reference_id = matches.value_of("reference_id").unwrap_or("playerid");
+    let other_id = matches.value_of("other_id").unwrap_or("playerid");
+    println!("Comparing {} to {}", reference_file, other_file);
+
+    let reference = compare::ProjectionFile::load(Path::new(reference_file), reference_id, &aliases)
+        .unwrap_or_else(|err| {
+            println!("Unable to load {}: {}", reference_file, err);
+            exit(1);
+        });
+    let other = compare::ProjectionFile::load(Path::new(other_file), other_id, &aliases)
+        .unwrap_or_else(|err| {
+            println!("Unable to load {}: {}", other_file, err);
+            exit(1);
+        });
+    compare::compare(&reference, &other);
+}
+
 // Free-standing function to make it simpler to see how the weights are converted from the
 // commandline arguments to something usable.
 fn split_weights(weights: &str) -> Result<Vec<f32>, std::num::ParseFloatError> {
     weights.split(",").map(str::trim).map(f32::from_str).collect()
 }
+
+// Last season gets the same weight as with the default weights so the regression amounts keep
+// their meaning. Each season before that counts for less, halving every `half_life` seasons.
+fn half_life_weights(first: f32, half_life: f32, seasons: usize) -> Vec<f32> {
+    (0..seasons)
+        .map(|years_back| first * 0.5_f32.powf(years_back as f32 / half_life))
+        .collect()
+}
+
+// Rate stats named on the commandline, separated by commas. The special value "none" adds none
+// of them.
+fn split_rates<R: FromStr<Err = String> + Copy>(rates: &str, all: &[R]) -> Result<Vec<R>, String> {
+    match rates.trim().to_lowercase().as_str() {
+        "none" => Ok(Vec::new()),
+        "all" => Ok(all.to_vec()),
+        _ => rates.split(',').map(R::from_str).collect(),
+    }
+}
+
+// Seed simulations from the clock when no seed is given. The seed is printed so the run can be
+// repeated.
+fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+// Component regression amounts from the commandline, given as STAT=AMOUNT pairs. Without any, every
+// component regresses by the overall amount. The special value "stabilization" adds the
+// stabilization points, which later pairs can override, and "none" removes every amount.
+fn split_components<S>(stabilization: &[(S, u16)], amounts: Option<&str>)
+    -> Result<BTreeMap<S, u16>, String>
+    where S: FromStr<Err = String> + Ord + Copy
+{
+    let mut components = BTreeMap::new();
+    let amounts = match amounts {
+        Some(amounts) => amounts,
+        None => return Ok(components),
+    };
+
+    for pair in amounts.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        if pair.eq_ignore_ascii_case("stabilization") {
+            components.extend(stabilization.iter().cloned());
+            continue;
+        }
+        if pair.eq_ignore_ascii_case("none") {
+            components.clear();
+            continue;
+        }
+        let mut parts = pair.splitn(2, '=');
+        let stat = S::from_str(parts.next().unwrap_or(""))?;
+        let amount = parts.next()
+            .ok_or_else(|| format!("Expected STAT=AMOUNT but got '{}'", pair))
+            .and_then(|amount| u16::from_str(amount.trim())
+                      .map_err(|err| format!("Unable to parse '{}': {}", pair, err)))?;
+        components.insert(stat, amount);
+    }
+    Ok(components)
+}
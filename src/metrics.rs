@@ -0,0 +1,136 @@
+// Variances at or below this are treated as no variance at all, so a correlation with values that
+// barely move is undefined rather than noise.
+const MIN_VARIANCE: f64 = 1e-12;
+
+
+/// Running error statistics between predicted and actual values. Every pair can carry a weight,
+/// such as the number of PA, so that larger samples count for more.
+#[derive(Debug, Default)]
+pub struct ErrorMetrics {
+    weight: f64,
+    error: f64,
+    abs_error: f64,
+    sq_error: f64,
+    // Running means and sums of squared deviations from them, updated the way Welford's
+    // algorithm does so nearly constant values do not cancel out.
+    mean_predicted: f64,
+    mean_actual: f64,
+    predicted_m2: f64,
+    actual_m2: f64,
+    comoment: f64,
+}
+
+impl ErrorMetrics {
+    pub fn new() -> Self {
+        ErrorMetrics::default()
+    }
+
+    pub fn add(&mut self, predicted: f32, actual: f32) {
+        self.add_weighted(predicted, actual, 1.0);
+    }
+
+    pub fn add_weighted(&mut self, predicted: f32, actual: f32, weight: f32) {
+        let predicted = predicted as f64;
+        let actual = actual as f64;
+        let weight = weight as f64;
+        if weight <= 0.0 {
+            return;
+        }
+        let error = predicted - actual;
+        self.weight += weight;
+        self.error += weight * error;
+        self.abs_error += weight * error.abs();
+        self.sq_error += weight * error * error;
+
+        let delta_predicted = predicted - self.mean_predicted;
+        let delta_actual = actual - self.mean_actual;
+        self.mean_predicted += delta_predicted * weight / self.weight;
+        self.mean_actual += delta_actual * weight / self.weight;
+        self.predicted_m2 += weight * delta_predicted * (predicted - self.mean_predicted);
+        self.actual_m2 += weight * delta_actual * (actual - self.mean_actual);
+        self.comoment += weight * delta_predicted * (actual - self.mean_actual);
+    }
+
+    pub fn rmse(&self) -> f64 {
+        (self.sq_error / self.weight).sqrt()
+    }
+
+    pub fn mae(&self) -> f64 {
+        self.abs_error / self.weight
+    }
+
+    /// Mean of predicted minus actual. Positive means the projections run high.
+    pub fn bias(&self) -> f64 {
+        self.error / self.weight
+    }
+
+    /// Variance of the errors around their mean.
+    pub fn variance(&self) -> f64 {
+        let bias = self.bias();
+        (self.sq_error / self.weight - bias * bias).max(0.0)
+    }
+
+    pub fn stddev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// Pearson correlation between the predicted and actual values. It is NaN when either side
+    /// does not vary.
+    pub fn correlation(&self) -> f64 {
+        let var_predicted = self.predicted_m2 / self.weight;
+        let var_actual = self.actual_m2 / self.weight;
+        if !(var_predicted > MIN_VARIANCE && var_actual > MIN_VARIANCE) {
+            return f64::NAN;
+        }
+        let correlation = self.comoment / self.weight / (var_predicted * var_actual).sqrt();
+        correlation.clamp(-1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(pairs: &[(f32, f32)]) -> ErrorMetrics {
+        let mut metrics = ErrorMetrics::new();
+        for (predicted, actual) in pairs {
+            metrics.add(*predicted, *actual);
+        }
+        metrics
+    }
+
+    #[test]
+    fn errors() {
+        let metrics = metrics(&[(1.0, 2.0), (3.0, 2.0), (5.0, 2.0)]);
+        assert!((metrics.bias() - 1.0).abs() < 1e-12);
+        assert!((metrics.mae() - 5.0 / 3.0).abs() < 1e-12);
+        assert!((metrics.rmse() - (11.0f64 / 3.0).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn correlation() {
+        let pairs = [(1.0, 10.0), (2.0, 20.0), (4.0, 40.0)];
+        assert!((metrics(&pairs).correlation() - 1.0).abs() < 1e-12);
+        let pairs = [(1.0, 40.0), (2.0, 20.0), (4.0, 10.0)];
+        assert!(metrics(&pairs).correlation() < 0.0);
+    }
+
+    #[test]
+    fn correlation_without_variance() {
+        // Large, nearly equal values are where the one pass formula cancels out.
+        let pairs = [(1e6, 1.0), (1e6, 2.0), (1e6, 0.0)];
+        assert!(metrics(&pairs).correlation().is_nan());
+        let pairs = [(0.1, 1.0), (0.1, 2.0), (0.1, 0.0)];
+        assert!(metrics(&pairs).correlation().is_nan());
+        assert!(ErrorMetrics::new().correlation().is_nan());
+    }
+
+    #[test]
+    fn weighted_correlation() {
+        let mut metrics = ErrorMetrics::new();
+        metrics.add_weighted(1.0, 1.0, 600.0);
+        metrics.add_weighted(2.0, 3.0, 50.0);
+        metrics.add_weighted(3.0, 5.0, 200.0);
+        assert!((metrics.correlation() - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+
+// Share of a minor league batting line that carries over to the majors at each level. Pitchers
+// use the inverse, so a AAA pitcher allows more hits and strikes out fewer in the majors.
+const DEFAULT_FACTORS: &[(&str, f32)] = &[
+    ("AAA", 0.85),
+    ("AA", 0.75),
+    ("A+", 0.65),
+    ("A", 0.60),
+    ("A-", 0.55),
+    ("RK", 0.50),
+];
+
+
+/// Factors that translate minor league seasons into their MLB equivalents, keyed by level.
+pub struct Translations {
+    factors: HashMap<String, f32>,
+}
+
+impl Translations {
+    pub fn new() -> Self {
+        let factors = DEFAULT_FACTORS.iter()
+            .map(|(level, factor)| (level.to_string(), *factor))
+            .collect();
+        Translations { factors }
+    }
+
+    /// Set factors from LEVEL=FACTOR pairs, such as `AAA=0.9,AA=0.8`. New levels are added.
+    pub fn add(&mut self, factors: &str) -> Result<(), String> {
+        for pair in factors.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let mut parts = pair.splitn(2, '=');
+            let level = parts.next().map(str::trim).unwrap_or("");
+            let factor = parts.next()
+                .ok_or_else(|| format!("Expected LEVEL=FACTOR but got '{}'", pair))
+                .and_then(|factor| factor.trim().parse::<f32>()
+                          .map_err(|err| format!("Unable to parse '{}': {}", pair, err)))?;
+            if level.is_empty() || factor <= 0.0 {
+                return Err(format!("Expected LEVEL=FACTOR with a positive factor but got '{}'",
+                                   pair));
+            }
+            self.factors.insert(level.to_uppercase(), factor);
+        }
+        Ok(())
+    }
+
+    /// The factor for a level, ignoring case.
+    pub fn factor(&self, level: &str) -> Option<f32> {
+        self.factors.get(&level.trim().to_uppercase()).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_factors() {
+        let translations = Translations::new();
+        assert_eq!(translations.factor("AAA"), Some(0.85));
+        assert_eq!(translations.factor(" aa "), Some(0.75));
+        assert_eq!(translations.factor("Rk"), Some(0.50));
+        assert_eq!(translations.factor("MLB"), None);
+    }
+
+    #[test]
+    fn add_factors() {
+        let mut translations = Translations::new();
+        translations.add("aaa=0.9, DSL=0.4").unwrap();
+        assert_eq!(translations.factor("AAA"), Some(0.9));
+        assert_eq!(translations.factor("dsl"), Some(0.4));
+        assert_eq!(translations.factor("AA"), Some(0.75));
+        assert!(translations.add("AAA=0").is_err());
+        assert!(translations.add("AAA").is_err());
+        assert!(translations.add("=0.5").is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::aging::AgingCurve;
+use crate::playingtime::PlayingTime;
+use crate::projection::{BattingContext, Parameters, PitchingContext};
+
+
+/// How projections are written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Csv,
+    /// A pretty printed object with the metadata and an array of projections.
+    Json,
+    /// The metadata on the first line and then one projection per line.
+    JsonLines,
+}
+
+/// Anything that can go wrong writing projections.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+}
+
+/// What produced a set of projections, written ahead of them in the JSON formats.
+#[derive(Serialize)]
+pub struct Metadata<'a> {
+    generator: &'static str,
+    version: &'static str,
+    year: u16,
+    /// First and last seasons the projections are built from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    history: Option<History>,
+    parameters: ParameterMetadata<'a>,
+    /// League context the projections were finished with, estimated for the year.
+    league: LeagueMetadata<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum LeagueMetadata<'a> {
+    Batting(&'a BattingContext),
+    Pitching(&'a PitchingContext),
+}
+
+#[derive(Serialize)]
+struct History {
+    first: u16,
+    last: u16,
+}
+
+#[derive(Serialize)]
+struct ParameterMetadata<'a> {
+    peak_age: u8,
+    age_convention: &'static str,
+    aging: AgingMetadata,
+    batter_regress: u16,
+    starter_regress: u16,
+    reliever_regress: u16,
+    batter_components: BTreeMap<&'static str, u16>,
+    pitcher_components: BTreeMap<&'static str, u16>,
+    batter_weights: &'a [f32],
+    pitcher_weights: &'a [f32],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalize_era: Option<&'static str>,
+    minors_weight: f32,
+    playing_time: &'a PlayingTime,
+    reapply_park: bool,
+    mlb_baseline: bool,
+    era_blend: f32,
+    calibrate_baseruns: bool,
+}
+
+#[derive(Serialize)]
+struct AgingMetadata {
+    batting: BTreeMap<&'static str, AgingCurve>,
+    pitching: BTreeMap<&'static str, AgingCurve>,
+}
+
+#[derive(Serialize)]
+struct Document<'a, T> {
+    metadata: &'a Metadata<'a>,
+    projections: &'a [T],
+}
+
+#[derive(Serialize)]
+struct Header<'a> {
+    metadata: &'a Metadata<'a>,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::JsonLines => "jsonl",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" | "json-lines" => Ok(OutputFormat::JsonLines),
+            _ => Err(format!("Unknown format '{}', expected csv, json or jsonl", format)),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Csv(err) => write!(f, "{}", err),
+            Error::Json(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Self {
+        Error::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl<'a> Metadata<'a> {
+    pub fn batting(year: u16, history: Option<(u16, u16)>, parameters: &'a Parameters,
+                   context: &'a BattingContext) -> Self {
+        Metadata::new(year, history, parameters, LeagueMetadata::Batting(context))
+    }
+
+    pub fn pitching(year: u16, history: Option<(u16, u16)>, parameters: &'a Parameters,
+                    context: &'a PitchingContext) -> Self {
+        Metadata::new(year, history, parameters, LeagueMetadata::Pitching(context))
+    }
+
+    fn new(year: u16, history: Option<(u16, u16)>, parameters: &'a Parameters,
+           league: LeagueMetadata<'a>) -> Self {
+        let aging = AgingMetadata {
+            batting: parameters.aging.batting_curves()
+                .map(|(stat, curve)| (stat.name(), *curve))
+                .collect(),
+            pitching: parameters.aging.pitching_curves()
+                .map(|(stat, curve)| (stat.name(), *curve))
+                .collect(),
+        };
+        Metadata {
+            generator: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            year,
+            history: history.map(|(first, last)| History { first, last }),
+            parameters: ParameterMetadata {
+                peak_age: parameters.peak_age,
+                age_convention: parameters.age_convention.name(),
+                aging,
+                batter_regress: parameters.batter_regress,
+                starter_regress: parameters.starter_regress,
+                reliever_regress: parameters.reliever_regress,
+                batter_components: parameters.batter_components.iter()
+                    .map(|(stat, amount)| (stat.name(), *amount))
+                    .collect(),
+                pitcher_components: parameters.pitcher_components.iter()
+                    .map(|(stat, amount)| (stat.name(), *amount))
+                    .collect(),
+                batter_weights: &parameters.batter_weights,
+                pitcher_weights: &parameters.pitcher_weights,
+                normalize_era: parameters.era.map(|target| target.name()),
+                minors_weight: parameters.minors_weight,
+                playing_time: &parameters.playing_time,
+                reapply_park: parameters.reapply_park,
+                mlb_baseline: parameters.mlb_baseline,
+                era_blend: parameters.era_blend,
+                calibrate_baseruns: parameters.calibrate_baseruns,
+            },
+            league,
+        }
+    }
+}
+
+/// Write projections to `name` with the format's extension. CSV leaves out the metadata.
+pub fn write<T: Serialize>(projections: &[T], name: &str, format: OutputFormat,
+                           metadata: &Metadata) -> Result<(), Error> {
+    let output_file = format!("{}.{}", name, format.extension());
+    match format {
+        OutputFormat::Csv => {
+            let mut wtr = csv::Writer::from_path(&output_file)?;
+            for projection in projections {
+                wtr.serialize(projection)?;
+            }
+            wtr.flush()?;
+        }
+        OutputFormat::Json => {
+            let mut wtr = BufWriter::new(File::create(&output_file)?);
+            serde_json::to_writer_pretty(&mut wtr, &Document { metadata, projections })?;
+            writeln!(wtr)?;
+            wtr.flush()?;
+        }
+        OutputFormat::JsonLines => {
+            let mut wtr = BufWriter::new(File::create(&output_file)?);
+            serde_json::to_writer(&mut wtr, &Header { metadata })?;
+            writeln!(wtr)?;
+            for projection in projections {
+                serde_json::to_writer(&mut wtr, projection)?;
+                writeln!(wtr)?;
+            }
+            wtr.flush()?;
+        }
+    }
+    Ok(())
+}
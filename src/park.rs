@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+
+/// How a park changes each component compared to a neutral park, as a ratio where 1.0 is
+/// neutral. These are full season factors, with home and road already blended. `h` applies to
+/// singles, since doubles, triples and home runs have their own factors.
+#[derive(Clone, Copy, Debug)]
+pub struct ParkFactor {
+    pub r: f32,
+    pub h: f32,
+    pub double: f32,
+    pub triple: f32,
+    pub hr: f32,
+    pub so: f32,
+    pub bb: f32,
+}
+
+/// Park factors for each team, by year.
+pub struct ParkFactors {
+    parks: HashMap<String, BTreeMap<u16, ParkFactor>>,
+}
+
+#[derive(Deserialize)]
+struct RawParkFactor {
+    teamid: String,
+    yearid: u16,
+    #[serde(default)]
+    r: Option<f32>,
+    #[serde(default)]
+    h: Option<f32>,
+    #[serde(default, rename = "2b")]
+    double: Option<f32>,
+    #[serde(default, rename = "3b")]
+    triple: Option<f32>,
+    #[serde(default)]
+    hr: Option<f32>,
+    #[serde(default)]
+    so: Option<f32>,
+    #[serde(default)]
+    bb: Option<f32>,
+}
+
+impl ParkFactors {
+    /// Load a CSV file with teamID and yearID columns and any of R, H, 2B, 3B, HR, SO and BB.
+    /// Column names are case insensitive and missing factors are neutral. Factors have to be
+    /// positive numbers.
+    pub fn from_path(path: &Path) -> Result<Self, csv::Error> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let headers = reader.headers()?
+            .iter()
+            .map(str::to_lowercase)
+            .collect();
+        reader.set_headers(headers);
+
+        let mut parks: HashMap<String, BTreeMap<u16, ParkFactor>> = HashMap::new();
+        for record in reader.deserialize() {
+            let record: RawParkFactor = record?;
+            let factor = ParkFactor {
+                r: record.r.unwrap_or(1.0),
+                h: record.h.unwrap_or(1.0),
+                double: record.double.unwrap_or(1.0),
+                triple: record.triple.unwrap_or(1.0),
+                hr: record.hr.unwrap_or(1.0),
+                so: record.so.unwrap_or(1.0),
+                bb: record.bb.unwrap_or(1.0),
+            };
+            let factors = [("R", factor.r), ("H", factor.h), ("2B", factor.double),
+                           ("3B", factor.triple), ("HR", factor.hr), ("SO", factor.so),
+                           ("BB", factor.bb)];
+            for (name, value) in &factors {
+                if !(value.is_finite() && *value > 0.0) {
+                    let message = format!("Park factor {} for {} in {} is {}, expected a positive \
+                                           number", name, record.teamid, record.yearid, value);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message).into());
+                }
+            }
+            parks.entry(record.teamid)
+                .or_default()
+                .insert(record.yearid, factor);
+        }
+        Ok(ParkFactors { parks })
+    }
+
+    /// The team's park in a year.
+    pub fn get(&self, teamid: &str, year: u16) -> Option<&ParkFactor> {
+        self.parks.get(teamid).and_then(|years| years.get(&year))
+    }
+
+    /// The team's park in a year or, when that year is missing, the latest year before it.
+    pub fn latest(&self, teamid: &str, year: u16) -> Option<&ParkFactor> {
+        self.parks.get(teamid)
+            .and_then(|years| years.range(..=year).next_back())
+            .map(|(_, factor)| factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn load(name: &str, contents: &str) -> Result<ParkFactors, csv::Error> {
+        let path = std::env::temp_dir()
+            .join(format!("capuchin-{}-parks-{}.csv", std::process::id(), name));
+        fs::write(&path, contents).expect("Unable to write test file.");
+        let parks = ParkFactors::from_path(&path);
+        fs::remove_file(&path).ok();
+        parks
+    }
+
+    #[test]
+    fn load_factors() {
+        let parks = load("valid", "teamID,yearID,R,HR\nCOL,2014,1.15,\nCOL,2015,1.12,1.10\n")
+            .unwrap();
+        let park = parks.get("COL", 2014).unwrap();
+        assert_eq!(park.r, 1.15);
+        assert_eq!(park.hr, 1.0);
+        assert_eq!(parks.latest("COL", 2017).unwrap().hr, 1.10);
+        assert!(parks.get("SEA", 2014).is_none());
+    }
+
+    #[test]
+    fn reject_bad_factors() {
+        for (name, value) in &[("zero", "0"), ("negative", "-1.1"), ("nan", "NaN"),
+                               ("inf", "inf")] {
+            let contents = format!("teamID,yearID,R,HR\nCOL,2014,1.15,1.1\nSEA,2015,0.95,{}\n",
+                                   value);
+            let err = load(name, &contents).err().expect("Expected a bad factor to fail.");
+            let message = err.to_string();
+            assert!(message.contains("HR for SEA in 2015"), "{}", message);
+        }
+    }
+}
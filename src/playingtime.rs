@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+
+// Marcel gives every batter 200 PA, starters 60 IP and relievers 25 IP on top of half of last
+// season and a tenth of the season before.
+const MARCEL_BATTER_BASELINE: f32 = 200.0;
+const MARCEL_STARTER_BASELINE: f32 = 60.0;
+const MARCEL_RELIEVER_BASELINE: f32 = 25.0;
+const MARCEL_COEFFICIENTS: &[f32] = &[0.5, 0.1];
+// Innings a typical start lasts. Used to tell starters from relievers in a depth chart.
+const IP_PER_START: f32 = 5.5;
+
+
+/// Projects PA and IP as a baseline plus a share of each previous season.
+#[derive(Clone, Debug, Serialize)]
+pub struct PlayingTime {
+    /// PA every batter starts with.
+    pub batter_baseline: f32,
+    /// IP every starter starts with.
+    pub starter_baseline: f32,
+    /// IP every reliever starts with.
+    pub reliever_baseline: f32,
+    /// Share of each previous season's PA or IP to add, starting with last season.
+    pub coefficients: Vec<f32>,
+}
+
+/// Playing time for a player from outside of Capuchin, such as a depth chart. Anything missing
+/// falls back to the formula.
+#[derive(Debug)]
+pub struct Target {
+    pub pa: Option<f32>,
+    pub ip: Option<f32>,
+    pub gs: Option<f32>,
+}
+
+/// Playing time targets keyed by player.
+pub struct DepthChart {
+    targets: HashMap<String, Target>,
+}
+
+#[derive(Deserialize)]
+struct RawTarget {
+    playerid: String,
+    #[serde(default)]
+    pa: Option<f32>,
+    #[serde(default)]
+    ip: Option<f32>,
+    #[serde(default)]
+    gs: Option<f32>,
+}
+
+impl PlayingTime {
+    pub fn marcel() -> Self {
+        PlayingTime {
+            batter_baseline: MARCEL_BATTER_BASELINE,
+            starter_baseline: MARCEL_STARTER_BASELINE,
+            reliever_baseline: MARCEL_RELIEVER_BASELINE,
+            coefficients: Vec::from(MARCEL_COEFFICIENTS),
+        }
+    }
+
+    /// PA or IP carried forward from the seasons before `year`. The lookback is as many seasons
+    /// as there are coefficients and `amount` gives the player's PA or IP in a year.
+    pub fn previous_seasons<F: Fn(u16) -> u16>(&self, year: u16, amount: F) -> f32 {
+        self.coefficients.iter()
+            .zip(1..)
+            .filter_map(|(coefficient, years_back)| {
+                year.checked_sub(years_back)
+                    .map(|season_year| coefficient * amount(season_year) as f32)
+            })
+            .sum()
+    }
+}
+
+impl Target {
+    /// Whether the pitcher's innings mostly come from starting. Only known when GS is given.
+    pub fn is_starter(&self) -> Option<bool> {
+        let gs = self.gs?;
+        let ip = self.ip.unwrap_or(0.0);
+        Some(gs > 0.0 && gs * IP_PER_START >= ip / 2.0)
+    }
+}
+
+impl DepthChart {
+    /// Load a CSV file with a playerID column and any of PA, IP and GS. Column names are case
+    /// insensitive and empty values are left to the formula.
+    pub fn from_path(path: &Path) -> Result<Self, csv::Error> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let headers = reader.headers()?
+            .iter()
+            .map(str::to_lowercase)
+            .collect();
+        reader.set_headers(headers);
+
+        let mut targets = HashMap::new();
+        for record in reader.deserialize() {
+            let record: RawTarget = record?;
+            let target = Target {
+                pa: record.pa,
+                ip: record.ip,
+                gs: record.gs,
+            };
+            targets.insert(record.playerid, target);
+        }
+        Ok(DepthChart { targets })
+    }
+
+    pub fn get(&self, playerid: &str) -> Option<&Target> {
+        self.targets.get(playerid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn marcel_playing_time() {
+        let playing_time = PlayingTime::marcel();
+        let pa = |year| match year {
+            2014 => 600,
+            2013 => 400,
+            2012 => 650,
+            _ => 0,
+        };
+        assert_eq!(playing_time.batter_baseline + playing_time.previous_seasons(2015, pa), 540.0);
+        let ip = |year| match year {
+            2014 => 180,
+            2013 => 150,
+            _ => 0,
+        };
+        assert_eq!(playing_time.starter_baseline + playing_time.previous_seasons(2015, ip), 165.0);
+        assert_eq!(playing_time.reliever_baseline + playing_time.previous_seasons(2015, ip), 130.0);
+        // Nothing before year 0 to look back on.
+        assert_eq!(playing_time.previous_seasons(0, |_| 100), 0.0);
+    }
+
+    #[test]
+    fn depth_chart() {
+        let path = std::env::temp_dir()
+            .join(format!("capuchin-{}-depth-chart.csv", std::process::id()));
+        fs::write(&path, "playerID,PA,IP,GS\nbat01,550,,\nsp01,,180,30\nrp01,,65,0\n")
+            .expect("Unable to write test file.");
+        let chart = DepthChart::from_path(&path).expect("Unable to load test depth chart.");
+        fs::remove_file(&path).ok();
+
+        let batter = chart.get("bat01").unwrap();
+        assert_eq!(batter.pa, Some(550.0));
+        assert_eq!(batter.ip, None);
+        assert_eq!(batter.is_starter(), None);
+        let starter = chart.get("sp01").unwrap();
+        assert_eq!(starter.ip, Some(180.0));
+        assert_eq!(starter.is_starter(), Some(true));
+        assert_eq!(chart.get("rp01").unwrap().is_starter(), Some(false));
+        assert!(chart.get("missing").is_none());
+    }
+}
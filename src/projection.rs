@@ -1,46 +1,168 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::BTreeMap;
 use std::path::Path;
 
+use serde::Serialize;
 
+use crate::aging;
+use crate::baseruns;
+use crate::era;
+use crate::fip;
+use crate::linearweights;
+use crate::minors;
+use crate::park;
+use crate::playingtime;
 use crate::register;
+use crate::roster;
 use crate::databank;
 
 
+/// Largest IP starters and relievers can be regressed by. Internally the amount is in outs, which
+/// has to fit in a u16.
+pub const MAX_PITCHER_REGRESS: u16 = u16::MAX / 3;
+
+
+/// Knobs of the projection model. These can be changed without reloading any data.
+#[derive(Clone, Debug)]
+pub struct Parameters {
+    pub peak_age: u8,
+    /// Which date in the season ages are calculated on.
+    pub age_convention: register::AgeConvention,
+    /// How each stat changes as players move away from `peak_age`.
+    pub aging: aging::AgingModel,
+    /// Number of league average PA to regress batters.
+    pub batter_regress: u16,
+    /// Number of league average IP to regress starters.
+    pub starter_regress: u16,
+    /// Number of league average IP to regress relievers.
+    pub reliever_regress: u16,
+    /// PA to regress individual batting components instead of `batter_regress`.
+    pub batter_components: BTreeMap<databank::BattingStat, u16>,
+    /// IP to regress individual pitching components instead of the starter or reliever amount.
+    pub pitcher_components: BTreeMap<databank::PitchingStat, u16>,
+    pub batter_weights: Vec<f32>,
+    pub pitcher_weights: Vec<f32>,
+    /// League environment to rescale past seasons to, if any.
+    pub era: Option<era::EraTarget>,
+    /// Weight of a minor league season relative to an MLB season from the same year.
+    pub minors_weight: f32,
+    /// How many PA and IP to project.
+    pub playing_time: playingtime::PlayingTime,
+    /// Move neutral projections into the park of the player's latest team.
+    pub reapply_park: bool,
+    /// Regress toward MLB-wide means instead of the means of the leagues the player was in.
+    pub mlb_baseline: bool,
+    /// Share of ERA that comes from Base Runs rather than projected earned runs.
+    pub era_blend: f32,
+    /// Coefficients of the Base Runs estimate of earned runs.
+    pub baseruns: baseruns::BaseRuns,
+    /// Set the Base Runs multiplier so each season's league earned runs are matched.
+    pub calibrate_baseruns: bool,
+}
+
+/// League context a year's batting projections were finished with.
+#[derive(Clone, Debug, Serialize)]
+pub struct BattingContext {
+    /// Missing when no season had the totals to estimate them.
+    pub linear_weights: Option<linearweights::LinearWeights>,
+}
+
+/// League context a year's pitching projections were finished with.
+#[derive(Clone, Debug, Serialize)]
+pub struct PitchingContext {
+    /// Missing when no season had the totals to estimate them.
+    pub fip_constants: Option<fip::FipConstants>,
+    /// The coefficients used, with the multiplier calibrated if that was asked for.
+    pub baseruns: baseruns::BaseRuns,
+}
+
 pub struct Capuchin {
-    peak_age: u8,
-    batter_regress: u16,
-    starter_regress: u16,
-    reliever_regress: u16,
-    batter_weights: Vec<f32>,
-    pitcher_weights: Vec<f32>,
+    parameters: Parameters,
     people: Option<register::People>,
+    // Playing time that replaces the formula for the players listed.
+    depth_chart: Option<playingtime::DepthChart>,
+    // Players to project at replacement level when they have no MLB history.
+    roster: Option<roster::Roster>,
+    // Seasons are neutralized with these before being combined.
+    park_factors: Option<park::ParkFactors>,
+    // Write names, handedness and other IDs from the register next to each projection.
+    player_info: bool,
+    // Flag two-way players in the projections. Only once their thresholds are set.
+    two_way: bool,
+    // Rate stats to add to each batting projection.
+    batting_rates: Vec<databank::BattingRate>,
+    // Rate stats to add to each pitching projection.
+    pitching_rates: Vec<databank::PitchingRate>,
     players: databank::Players,
     batting_league_totals: BTreeMap<u16, databank::BattingSeasonSummaryRates>,
     pitching_league_totals: BTreeMap<u16, databank::PitchingSeasonSummaryRates>,
+    // Totals for each league (AL, NL, ...) within a year.
+    batting_split_totals: BTreeMap<(u16, String), databank::BattingSeasonSummaryRates>,
+    pitching_split_totals: BTreeMap<(u16, String), databank::PitchingSeasonSummaryRates>,
 }
 
 impl Capuchin {
-    pub fn new(peak_age: u8, batter_regress: u16, starter_regress: u16, reliever_regress: u16, batter_weights: Vec<f32>, pitcher_weights: Vec<f32>) -> Self {
+    pub fn new(parameters: Parameters) -> Self {
         Capuchin {
-            peak_age: peak_age,
-            batter_weights: batter_weights,
-            pitcher_weights: pitcher_weights,
-            batter_regress: batter_regress,
-            // Multiply by three because internally using outs rather than IP.
-            starter_regress: starter_regress * 3,
-            reliever_regress: reliever_regress * 3,
+            parameters,
             people: None,
+            depth_chart: None,
+            roster: None,
+            park_factors: None,
+            player_info: false,
+            two_way: false,
+            batting_rates: Vec::new(),
+            pitching_rates: Vec::new(),
             players: databank::Players::new(),
             batting_league_totals: BTreeMap::new(),
             pitching_league_totals: BTreeMap::new(),
+            batting_split_totals: BTreeMap::new(),
+            pitching_split_totals: BTreeMap::new(),
         }
     }
 
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
+    pub fn set_parameters(&mut self, parameters: Parameters) {
+        self.parameters = parameters;
+    }
+
     pub fn load_register(&mut self, people: register::People) {
         self.people = Some(people);
     }
 
+    pub fn load_depth_chart(&mut self, depth_chart: playingtime::DepthChart) {
+        self.depth_chart = Some(depth_chart);
+    }
+
+    pub fn load_roster(&mut self, roster: roster::Roster) {
+        self.roster = Some(roster);
+    }
+
+    pub fn load_park_factors(&mut self, park_factors: park::ParkFactors) {
+        self.park_factors = Some(park_factors);
+    }
+
+    /// Add the player's names, handedness and other IDs to the projections when a register is
+    /// loaded.
+    pub fn include_player_info(&mut self, player_info: bool) {
+        self.player_info = player_info;
+    }
+
+    /// Choose the rate stats added to batting projections. None are added by default.
+    pub fn include_batting_rates(&mut self, rates: Vec<databank::BattingRate>) {
+        self.batting_rates = rates;
+    }
+
+    /// Choose the rate stats added to pitching projections. None are added by default.
+    pub fn include_pitching_rates(&mut self, rates: Vec<databank::PitchingRate>) {
+        self.pitching_rates = rates;
+    }
+
     pub fn load_batting(&mut self, batting_csv: &Path) -> Result<(), csv::Error> {
         self.players.load_batting(batting_csv)
     }
@@ -48,39 +170,130 @@ impl Capuchin {
     pub fn load_pitching(&mut self, pitching_csv: &Path) -> Result<(), csv::Error> {
         self.players.load_pitching(pitching_csv)
     }
+
+    pub fn load_minor_batting(&mut self, batting_csv: &Path, translations: &minors::Translations)
+        -> Result<(), csv::Error>
+    {
+        self.players.load_minor_batting(batting_csv, translations)
+    }
+
+    pub fn load_minor_pitching(&mut self, pitching_csv: &Path, translations: &minors::Translations)
+        -> Result<(), csv::Error>
+    {
+        self.players.load_minor_pitching(pitching_csv, translations)
+    }
+
+    /// Seasons with at least `pa` PA and `ip` IP make a two-way player, who keeps both
+    /// projections and is flagged in them.
+    pub fn set_two_way_thresholds(&mut self, pa: u16, ip: u16) {
+        self.players.set_two_way_thresholds(pa, ip);
+        self.two_way = true;
+    }
+
     /// Remove players playing out of position. This counts batters pitching and pitchers batting.
     pub fn remove_out_of_position_players(&mut self) {
         self.players.remove_out_of_position_players();
     }
 
-    pub fn batting_projection(&mut self, year: u16) -> Vec<databank::BattingProjection> {
+    /// Every player's real batting line for a single year.
+    pub fn batting_actuals(&self, year: u16) -> HashMap<&String, databank::BattingSeasonSummary> {
+        self.players.batting_summaries(year)
+    }
+
+    /// Every player's real pitching line for a single year.
+    pub fn pitching_actuals(&self, year: u16) -> HashMap<&String, databank::PitchingSeasonSummary> {
+        self.players.pitching_summaries(year)
+    }
+
+    /// League rates for a year. Only available once a projection has needed that year.
+    pub fn batting_league_rates(&self, year: u16) -> Option<&databank::BattingSeasonSummaryRates> {
+        self.batting_league_totals.get(&year)
+    }
+
+    /// League rates for a year. Only available once a projection has needed that year.
+    pub fn pitching_league_rates(&self, year: u16) -> Option<&databank::PitchingSeasonSummaryRates> {
+        self.pitching_league_totals.get(&year)
+    }
+
+    /// First and last seasons a batting projection for `year` is built from.
+    pub fn batting_history(&self, year: u16) -> Option<(u16, u16)> {
+        self.history(year, self.parameters.batter_weights.len())
+    }
+
+    /// First and last seasons a pitching projection for `year` is built from.
+    pub fn pitching_history(&self, year: u16) -> Option<(u16, u16)> {
+        self.history(year, self.parameters.pitcher_weights.len())
+    }
+
+    /// Project batters for `year`, along with the league context the projections used.
+    pub fn batting_projection(&mut self, year: u16)
+        -> (Vec<databank::BattingProjection>, BattingContext)
+    {
         // Calculate the totals for each season to get per-PA averages.
-        let number_years = self.batter_weights.len();
-        let start_year = year - number_years as u16;
-        let end_year = year - 1;
+        let number_years = self.parameters.batter_weights.len();
+        let (start_year, end_year) = match self.batting_history(year) {
+            Some(history) => history,
+            None => return (Vec::new(), BattingContext { linear_weights: None }),
+        };
         let past_seasons = self.players.batting_seasons(start_year, end_year);
 
         // Build a list of every player that appeared in those seasons. Each will get a projection.
         // Combine each player's split seasons into a single season summary.
+        // Also keep how much they played in each league to know which league mean to regress to.
+        // Seasons are put into a neutral park first when there are park factors.
+        let park_factors = self.park_factors.as_ref();
         let mut batters = HashMap::new();
+        let mut batter_leagues = HashMap::new();
         for season in &past_seasons {
             let player = batters.entry(season.playerid())
                 .or_insert(BTreeMap::new());
             let summary = player.entry(season.yearid())
                 .or_insert(databank::BattingSeasonSummary::new());
+            summary.mut_add_season(&neutral_batting(park_factors, season));
+            batter_leagues.entry((season.playerid(), *season.yearid()))
+                .or_insert_with(Vec::new)
+                .push((season.lgid(), *season.pa()));
+        }
+
+        // Minor league seasons, already in MLB terms, count toward the same players. Players with
+        // only minor league seasons get a projection too.
+        let mut minor_batters = HashMap::new();
+        for season in self.players.minor_batting_seasons(start_year, end_year) {
+            batters.entry(season.playerid()).or_insert(BTreeMap::new());
+            let player = minor_batters.entry(season.playerid())
+                .or_insert(BTreeMap::new());
+            let summary = player.entry(season.yearid())
+                .or_insert(databank::BattingSeasonSummary::new());
             summary.mut_add_season(season);
         }
 
         for year in start_year..year {
-            if !self.batting_league_totals.contains_key(&year) {
-                // TODO: Filter out pitcher seasons.
-                let season_summary = past_seasons.iter()
-                    .filter(|season| season.is_year(year))
-                    .fold(databank::BattingSeasonSummary::new(),
-                        |summary, &season| summary.add_season(season))
-                    ;
-                let summary_rates = season_summary.into();
-                self.batting_league_totals.insert(year, summary_rates);
+            if self.batting_league_totals.contains_key(&year) {
+                continue;
+            }
+            // Leave out players playing out of position, such as pitchers batting, so they don't
+            // drag down the league mean.
+            let mut season_summary = databank::BattingSeasonSummary::new();
+            let mut league_summaries = HashMap::new();
+            let league_seasons = past_seasons.iter()
+                .filter(|season| season.is_year(year) && self.players.player_is_batter(season));
+            for season in league_seasons {
+                let neutral_season = neutral_batting(park_factors, season);
+                season_summary.mut_add_season(&neutral_season);
+                // Seasons without a league only count toward MLB.
+                if !season.lgid().is_empty() {
+                    league_summaries.entry(season.lgid())
+                        .or_insert_with(databank::BattingSeasonSummary::new)
+                        .mut_add_season(&neutral_season);
+                }
+            }
+            // Years without any data, such as those after the last season loaded, have no totals.
+            if *season_summary.pa() == 0 {
+                continue;
+            }
+            self.batting_league_totals.insert(year, season_summary.into());
+            for (lgid, summary) in league_summaries {
+                self.batting_split_totals.insert((year, lgid.to_string()), summary.into());
             }
         }
 
@@ -89,91 +302,248 @@ impl Capuchin {
         // Make the first element be the year of the projection. This makes the math a bit easier
         // for indexing previous years.
         weights_map.push(0.0);
-        for weight in &self.batter_weights {
+        for weight in &self.parameters.batter_weights {
             weights_map.push(*weight);
         }
         let weights_map = weights_map;
 
+        let batter_components = self.parameters.batter_components.iter()
+            .map(|(stat, pa)| (*stat, *pa as f32))
+            .collect();
+
+        // Seasons are moved into the target era with the factors of the baseline they are
+        // regressed toward, the whole of MLB or each league on its own.
+        let (era_factors, league_era_factors) = match self.parameters.era {
+            Some(target) => {
+                let league_factors = if self.parameters.mlb_baseline {
+                    BTreeMap::new()
+                }
+                else {
+                    era::batting_league_factors(target, &self.batting_split_totals, start_year,
+                                                year)
+                };
+                (era::batting_factors(target, &self.batting_league_totals, start_year, year),
+                 league_factors)
+            }
+            None => (BTreeMap::new(), BTreeMap::new()),
+        };
+
+        // wOBA weights for the projected year, estimated the same way as the target era.
+        let linear_weights = linearweights::LinearWeights::estimate(
+            self.parameters.era.unwrap_or(era::EraTarget::Latest), &self.batting_league_totals,
+            start_year, year);
+
+        let with_history: HashSet<&String> = batters.keys().cloned().collect();
+
         // Weight player and league based on PA.
         let mut player_projections = Vec::with_capacity(batters.len());
         for (batter, batter_seasons) in batters {
             // Weighted batter seasons.
-            let mut weighted_batter = databank::IntBattingProjection::new_player(&batter, year);
+            let mut weighted_batter = databank::IntBattingProjection::new_player(batter, year);
             // What the league did with the same PAs, weighted the same.
             let mut batter_league_mean = databank::IntBattingProjection::league();
-            let mut projected_pa = 200.0;
+            // Two-way players split the baseline playing time between batting and pitching.
+            let two_way_share = self.players.two_way_batting_share(batter, start_year, end_year);
+            let playing_time = &self.parameters.playing_time;
+            let mut projected_pa = playing_time.batter_baseline * two_way_share.unwrap_or(1.0)
+                + playing_time.previous_seasons(year, |season_year| {
+                    self.players.season_pa(batter, season_year)
+                });
             for (season_year, season) in &batter_seasons {
+                // Only players out of position played that year, so there is no league to
+                // regress toward.
+                if !self.batting_league_totals.contains_key(*season_year) {
+                    continue;
+                }
                 let season_year = *season_year;
                 let season_pa = *season.pa() as u16;
-                projected_pa += match year - season_year {
-                    1 => 0.5 * season_pa as f32,
-                    2 => 0.1 * season_pa as f32,
-                    _ => 0.0,
-                };
                 let weight_idx = (year - season_year) as usize;
                 let weight = weights_map[weight_idx];
-                weighted_batter.weighted_add(season, weight);
+                // Keep the season and the league's line apart until both are in the target era.
+                let mut season_line = databank::IntBattingProjection::league();
+                season_line.weighted_add(season, weight);
+                let mut season_league = databank::IntBattingProjection::league();
 
-                let league_rate = self.batting_league_totals.get(&season_year)
-                    .expect("Expected to get a rate for this year.");
-                batter_league_mean.weighted_rate_add(season_pa, league_rate, weight);
+                if self.parameters.mlb_baseline {
+                    let league_rate = self.batting_league_totals.get(season_year)
+                        .expect("Expected to get a rate for this year.");
+                    season_league.weighted_rate_add(season_pa, league_rate, weight);
+                    for (stat, factor) in era_factors.get(season_year).into_iter().flatten() {
+                        season_line.adjust(*stat, *factor);
+                        season_league.adjust(*stat, *factor);
+                    }
+                }
+                else {
+                    // Each stint's league moves by its own factors and the season by the blend.
+                    let mut shares = Vec::new();
+                    for (lgid, stint_pa) in &batter_leagues[&(batter, *season_year)] {
+                        let split = (*season_year, lgid.to_string());
+                        let (league_rate, factors) = match self.batting_split_totals.get(&split) {
+                            Some(league_rate) => (league_rate, league_era_factors.get(&split)),
+                            // The league is unknown, or only pitchers batting (or batters
+                            // pitching) played in it.
+                            None => (self.batting_league_totals.get(season_year)
+                                         .expect("Expected to get a rate for this year."),
+                                     era_factors.get(season_year)),
+                        };
+                        let factors = factors.map_or(&[][..], Vec::as_slice);
+                        let mut stint_league = databank::IntBattingProjection::league();
+                        stint_league.weighted_rate_add(*stint_pa, league_rate, weight);
+                        for (stat, factor) in factors {
+                            stint_league.adjust(*stat, *factor);
+                        }
+                        season_league.add(&stint_league);
+                        shares.push((factors, *stint_pa as f32));
+                    }
+                    for (stat, factor) in era::blend(&shares) {
+                        season_line.adjust(stat, factor);
+                    }
+                }
+                weighted_batter.add(&season_line);
+                batter_league_mean.add(&season_league);
             }
 
+            // Minor league seasons are discounted on top of the usual weight.
+            for (season_year, season) in minor_batters.get(batter).into_iter().flatten() {
+                let league_rate = match self.batting_league_totals.get(season_year) {
+                    Some(league_rate) => league_rate,
+                    None => continue,
+                };
+                let weight = weights_map[(year - *season_year) as usize]
+                    * self.parameters.minors_weight;
+                let mut season_line = databank::IntBattingProjection::league();
+                season_line.weighted_add(season, weight);
+                let mut season_league = databank::IntBattingProjection::league();
+                season_league.weighted_rate_add(*season.pa() as u16, league_rate, weight);
+
+                for (stat, factor) in era_factors.get(season_year).into_iter().flatten() {
+                    season_line.adjust(*stat, *factor);
+                    season_league.adjust(*stat, *factor);
+                }
+                weighted_batter.add(&season_line);
+                batter_league_mean.add(&season_league);
+            }
+
+            let target = self.depth_chart.as_ref().and_then(|chart| chart.get(batter));
+            if let Some(pa) = target.and_then(|target| target.pa) {
+                projected_pa = pa;
+            }
             let projected_pa = projected_pa as u16;
-            let prorated_league_mean = batter_league_mean.prorate(self.batter_regress);
+            let prorated_league_mean = batter_league_mean.prorate(self.parameters.batter_regress);
             // Merge weighted player and league totals to regress the player.
-            weighted_batter.regress(&prorated_league_mean);
+            weighted_batter.regress(&prorated_league_mean, &batter_components);
 
             let mut projection = weighted_batter.prorate(projected_pa);
-            if let Some(ref people) = self.people {
-                people.find_by_bbref(&batter)
-                    .and_then(|p| p.get_age(year))
-                    .map(|age| {
-                        projection.set_age(age);
-                        let age_diff = self.peak_age as f32 - age as f32;
-                        if self.peak_age < age {
-                            projection.age_adjust(1.0 + (age_diff * 0.003));
-                        }
-                        else if self.peak_age > age {
-                            projection.age_adjust(1.0 + (age_diff * 0.006));
-                        }
-                    });
+            let person = self.people.as_ref().and_then(|people| people.find_by_playerid(batter));
+            self.age_batter(&mut projection, person, None, year);
+            let last_year = batter_seasons.keys().next_back().map(|last_year| **last_year);
+            if self.parameters.reapply_park {
+                if let Some(park) = self.projected_park(batter, last_year, year) {
+                    projection.apply_park(park);
+                }
+            }
+            let mut projection = projection.finalize(&self.batting_rates,
+                                                     linear_weights.as_ref());
+            if let Some(last_year) = last_year {
+                projection.set_team(self.players.stints(batter, last_year));
+            }
+            if self.two_way {
+                projection.set_two_way(two_way_share.is_some());
+            }
+            if let Some(info) = self.player_info(person) {
+                projection.set_player_info(info);
+            }
+            player_projections.push(projection);
+        }
+
+        let league_mean = self.replacement_batting_mean(year, start_year, &weights_map,
+                                                        &era_factors);
+        if let (Some(roster), Some(league_mean)) = (self.roster.as_ref(), league_mean) {
+            for entry in roster.batters().filter(|entry| !with_history.contains(&entry.playerid)) {
+                player_projections.push(self.replacement_batter(entry, year, &league_mean,
+                                                                linear_weights.as_ref()));
             }
-            player_projections.push(projection.finalize());
         }
 
         player_projections.sort();
-        player_projections
+        (player_projections, BattingContext { linear_weights })
     }
 
-    pub fn pitching_projection(&mut self, year: u16) -> Vec<databank::PitchingProjection> {
+    /// Project pitchers for `year`, along with the league context the projections used.
+    pub fn pitching_projection(&mut self, year: u16)
+        -> (Vec<databank::PitchingProjection>, PitchingContext)
+    {
         // Calculate the totals for each season to get per-PA averages.
-        let number_years = self.pitcher_weights.len();
-        let start_year = year - number_years as u16;
-        let end_year = year - 1;
+        let number_years = self.parameters.pitcher_weights.len();
+        let (start_year, end_year) = match self.pitching_history(year) {
+            Some(history) => history,
+            None => {
+                let context = PitchingContext {
+                    fip_constants: None,
+                    baseruns: self.parameters.baseruns.clone(),
+                };
+                return (Vec::new(), context);
+            }
+        };
         let past_seasons = self.players.pitching_seasons(start_year, end_year);
 
         // Build a list of every player that appeared in those seasons. Each will get a projection.
         // Combine each player's split seasons into a single season summary.
+        // Also keep how much they played in each league to know which league mean to regress to.
+        // Seasons are put into a neutral park first when there are park factors.
+        let park_factors = self.park_factors.as_ref();
         let mut pitchers = HashMap::new();
+        let mut pitcher_leagues = HashMap::new();
         for season in &past_seasons {
             let player = pitchers.entry(season.playerid())
                 .or_insert(BTreeMap::new());
             let summary = player.entry(season.yearid())
                 .or_insert(databank::PitchingSeasonSummary::new());
+            summary.mut_add_season(&neutral_pitching(park_factors, season));
+            pitcher_leagues.entry((season.playerid(), *season.yearid()))
+                .or_insert_with(Vec::new)
+                .push((season.lgid(), *season.ipouts()));
+        }
+
+        // Minor league seasons, already in MLB terms, count toward the same players. Players with
+        // only minor league seasons get a projection too.
+        let mut minor_pitchers = HashMap::new();
+        for season in self.players.minor_pitching_seasons(start_year, end_year) {
+            pitchers.entry(season.playerid()).or_insert(BTreeMap::new());
+            let player = minor_pitchers.entry(season.playerid())
+                .or_insert(BTreeMap::new());
+            let summary = player.entry(season.yearid())
+                .or_insert(databank::PitchingSeasonSummary::new());
             summary.mut_add_season(season);
         }
 
         for year in start_year..year {
-            if !self.pitching_league_totals.contains_key(&year) {
-                // TODO: Filter out pitcher seasons.
-                let season_summary = past_seasons.iter()
-                    .filter(|season| season.is_year(year))
-                    .fold(databank::PitchingSeasonSummary::new(),
-                        |summary, &season| summary.add_season(season))
-                    ;
-                let summary_rates = season_summary.into();
-                self.pitching_league_totals.insert(year, summary_rates);
+            if self.pitching_league_totals.contains_key(&year) {
+                continue;
+            }
+            // Leave out players playing out of position, such as pitchers batting, so they don't
+            // drag down the league mean.
+            let mut season_summary = databank::PitchingSeasonSummary::new();
+            let mut league_summaries = HashMap::new();
+            let league_seasons = past_seasons.iter()
+                .filter(|season| season.is_year(year) && self.players.player_is_pitcher(season));
+            for season in league_seasons {
+                let neutral_season = neutral_pitching(park_factors, season);
+                season_summary.mut_add_season(&neutral_season);
+                // Seasons without a league only count toward MLB.
+                if !season.lgid().is_empty() {
+                    league_summaries.entry(season.lgid())
+                        .or_insert_with(databank::PitchingSeasonSummary::new)
+                        .mut_add_season(&neutral_season);
+                }
+            }
+            // Years without any data, such as those after the last season loaded, have no totals.
+            if *season_summary.ipouts() == 0 {
+                continue;
+            }
+            self.pitching_league_totals.insert(year, season_summary.into());
+            for (lgid, summary) in league_summaries {
+                self.pitching_split_totals.insert((year, lgid.to_string()), summary.into());
             }
         }
 
@@ -182,32 +552,78 @@ impl Capuchin {
         // Make the first element be the year of the projection. This makes the math a bit easier
         // for indexing previous years.
         weights_map.push(0.0);
-        for weight in &self.pitcher_weights {
+        for weight in &self.parameters.pitcher_weights {
             weights_map.push(*weight);
         }
         let weights_map = weights_map;
 
+        // Multiply by three because internally using outs rather than IP.
+        let pitcher_components = self.parameters.pitcher_components.iter()
+            .map(|(stat, ip)| (*stat, *ip as f32 * 3.0))
+            .collect();
+
+        // Seasons are moved into the target era with the factors of the baseline they are
+        // regressed toward, the whole of MLB or each league on its own.
+        let (era_factors, league_era_factors) = match self.parameters.era {
+            Some(target) => {
+                let league_factors = if self.parameters.mlb_baseline {
+                    BTreeMap::new()
+                }
+                else {
+                    era::pitching_league_factors(target, &self.pitching_split_totals, start_year,
+                                                 year)
+                };
+                (era::pitching_factors(target, &self.pitching_league_totals, start_year, year),
+                 league_factors)
+            }
+            None => (BTreeMap::new(), BTreeMap::new()),
+        };
+
+        // FIP constants and Base Runs for the projected year, estimated the same way as the
+        // target era.
+        let target = self.parameters.era.unwrap_or(era::EraTarget::Latest);
+        let fip_constants = fip::FipConstants::estimate(target, &self.pitching_league_totals,
+                                                        start_year, year);
+        let baseruns = if self.parameters.calibrate_baseruns {
+            self.parameters.baseruns.calibrated(target, &self.pitching_league_totals, start_year,
+                                                year)
+        }
+        else {
+            self.parameters.baseruns.clone()
+        };
+
+        let with_history: HashSet<&String> = pitchers.keys().cloned().collect();
+
         // Weight player and league based on IP.
         let mut player_projections = Vec::with_capacity(pitchers.len());
         for (pitcher, pitcher_seasons) in pitchers {
             // Weighted pitcher seasons.
-            let mut weighted_pitcher = databank::IntPitchingProjection::new_player(&pitcher, year);
+            let mut weighted_pitcher = databank::IntPitchingProjection::new_player(pitcher, year);
             // What the league did with the same IPs, weighted the same.
             let mut pitcher_league_mean = databank::IntPitchingProjection::league();
-            let mut projected_ip = 200.0;
+            // Two-way players split the baseline playing time between batting and pitching.
+            let two_way_share = self.players.two_way_batting_share(pitcher, start_year, end_year);
+            let playing_time = &self.parameters.playing_time;
+            // Outs from previous seasons. The baseline is added once the role is known.
+            let mut projected_ip = playing_time.previous_seasons(year, |season_year| {
+                self.players.season_ipouts(pitcher, season_year)
+            });
             let mut reliever_seasons = 0;
             let mut starter_seasons = 0;
             for (season_year, season) in &pitcher_seasons {
+                // Only players out of position played that year, so there is no league to
+                // regress toward.
+                if !self.pitching_league_totals.contains_key(*season_year) {
+                    continue;
+                }
                 let season_year = *season_year;
                 let season_ip = *season.ipouts() as u16;
-                projected_ip += match year - season_year {
-                    1 => 0.5 * season_ip as f32,
-                    2 => 0.1 * season_ip as f32,
-                    _ => 0.0,
-                };
                 let weight_idx = (year - season_year) as usize;
                 let weight = weights_map[weight_idx];
-                weighted_pitcher.weighted_add(season, weight);
+                // Keep the season and the league's line apart until both are in the target era.
+                let mut season_line = databank::IntPitchingProjection::league();
+                season_line.weighted_add(season, weight);
+                let mut season_league = databank::IntPitchingProjection::league();
 
                 if season.is_reliever() {
                     reliever_seasons += 1;
@@ -216,40 +632,625 @@ impl Capuchin {
                     starter_seasons += 1;
                 }
 
-                let league_rate = self.pitching_league_totals.get(&season_year)
-                    .expect("Expected to get a rate for this year.");
-                pitcher_league_mean.weighted_rate_add(season_ip, league_rate, weight);
+                if self.parameters.mlb_baseline {
+                    let league_rate = self.pitching_league_totals.get(season_year)
+                        .expect("Expected to get a rate for this year.");
+                    season_league.weighted_rate_add(season_ip, league_rate, weight);
+                    for (stat, factor) in era_factors.get(season_year).into_iter().flatten() {
+                        season_line.adjust(*stat, *factor);
+                        season_league.adjust(*stat, *factor);
+                    }
+                }
+                else {
+                    // Each stint's league moves by its own factors and the season by the blend.
+                    let mut shares = Vec::new();
+                    for (lgid, stint_ipouts) in &pitcher_leagues[&(pitcher, *season_year)] {
+                        let split = (*season_year, lgid.to_string());
+                        let (league_rate, factors) = match self.pitching_split_totals.get(&split) {
+                            Some(league_rate) => (league_rate, league_era_factors.get(&split)),
+                            // The league is unknown, or only pitchers batting (or batters
+                            // pitching) played in it.
+                            None => (self.pitching_league_totals.get(season_year)
+                                         .expect("Expected to get a rate for this year."),
+                                     era_factors.get(season_year)),
+                        };
+                        let factors = factors.map_or(&[][..], Vec::as_slice);
+                        let mut stint_league = databank::IntPitchingProjection::league();
+                        stint_league.weighted_rate_add(*stint_ipouts, league_rate, weight);
+                        for (stat, factor) in factors {
+                            stint_league.adjust(*stat, *factor);
+                        }
+                        season_league.add(&stint_league);
+                        shares.push((factors, *stint_ipouts as f32));
+                    }
+                    for (stat, factor) in era::blend(&shares) {
+                        season_line.adjust(stat, factor);
+                    }
+                }
+                weighted_pitcher.add(&season_line);
+                pitcher_league_mean.add(&season_league);
             }
 
+            // Minor league seasons are discounted on top of the usual weight.
+            for (season_year, season) in minor_pitchers.get(pitcher).into_iter().flatten() {
+                let league_rate = match self.pitching_league_totals.get(season_year) {
+                    Some(league_rate) => league_rate,
+                    None => continue,
+                };
+                let weight = weights_map[(year - *season_year) as usize]
+                    * self.parameters.minors_weight;
+                let mut season_line = databank::IntPitchingProjection::league();
+                season_line.weighted_add(season, weight);
+                let mut season_league = databank::IntPitchingProjection::league();
+                season_league.weighted_rate_add(*season.ipouts() as u16, league_rate, weight);
+
+                for (stat, factor) in era_factors.get(season_year).into_iter().flatten() {
+                    season_line.adjust(*stat, *factor);
+                    season_league.adjust(*stat, *factor);
+                }
+                weighted_pitcher.add(&season_line);
+                pitcher_league_mean.add(&season_league);
+
+                if season.is_reliever() {
+                    reliever_seasons += 1;
+                }
+                else {
+                    starter_seasons += 1;
+                }
+            }
+
+            // A depth chart's GS decides the role, otherwise whatever the pitcher did most often.
+            let target = self.depth_chart.as_ref().and_then(|chart| chart.get(pitcher));
+            let is_starter = target.and_then(playingtime::Target::is_starter)
+                .unwrap_or(starter_seasons >= reliever_seasons);
+            let baseline = if is_starter {
+                playing_time.starter_baseline
+            } else {
+                playing_time.reliever_baseline
+            };
+            // Baselines are in IP but the projection uses outs.
+            projected_ip += 3.0 * baseline * two_way_share.map_or(1.0, |share| 1.0 - share);
+            if let Some(ip) = target.and_then(|target| target.ip) {
+                projected_ip = 3.0 * ip;
+            }
             let projected_ip = projected_ip as u16;
-            let regress_amount = if starter_seasons >= reliever_seasons {
-                self.starter_regress
+            let regress_amount = if is_starter {
+                self.parameters.starter_regress
             } else {
-                self.reliever_regress
+                self.parameters.reliever_regress
             };
+            // Multiply by three because internally using outs rather than IP.
+            let regress_amount = regress_amount * 3;
             let prorated_league_mean = pitcher_league_mean.prorate(regress_amount);
             // Merge weighted player and league totals to regress the player.
-            weighted_pitcher.regress(&prorated_league_mean);
+            weighted_pitcher.regress(&prorated_league_mean, &pitcher_components);
 
             let mut projection = weighted_pitcher.prorate(projected_ip);
-            if let Some(ref people) = self.people {
-                people.find_by_bbref(&pitcher)
-                    .and_then(|p| p.get_age(year))
-                    .map(|age| {
-                        projection.set_age(age);
-                        let age_diff = self.peak_age as f32 - age as f32;
-                        if self.peak_age < age {
-                            projection.age_adjust(1.0 + (age_diff * 0.003));
-                        }
-                        else if self.peak_age > age {
-                            projection.age_adjust(1.0 + (age_diff * 0.006));
-                        }
-                    });
+            let person = self.people.as_ref().and_then(|people| people.find_by_playerid(pitcher));
+            self.age_pitcher(&mut projection, person, None, year);
+            let last_year = pitcher_seasons.keys().next_back().map(|last_year| **last_year);
+            if self.parameters.reapply_park {
+                if let Some(park) = self.projected_park(pitcher, last_year, year) {
+                    projection.apply_park(park);
+                }
+            }
+            let mut projection = projection.finalize(&self.pitching_rates, fip_constants.as_ref(),
+                                                     &baseruns, self.parameters.era_blend);
+            if let Some(last_year) = last_year {
+                projection.set_team(self.players.stints(pitcher, last_year));
+            }
+            if self.two_way {
+                projection.set_two_way(two_way_share.is_some());
+            }
+            if let Some(info) = self.player_info(person) {
+                projection.set_player_info(info);
+            }
+            player_projections.push(projection);
+        }
+
+        let league_mean = self.replacement_pitching_mean(year, start_year, &weights_map,
+                                                         &era_factors);
+        if let (Some(roster), Some(league_mean)) = (self.roster.as_ref(), league_mean) {
+            for entry in roster.pitchers().filter(|entry| !with_history.contains(&entry.playerid)) {
+                player_projections.push(self.replacement_pitcher(entry, year, &league_mean,
+                                                                 fip_constants.as_ref(),
+                                                                 &baseruns));
             }
-            player_projections.push(projection.finalize());
         }
 
         player_projections.sort();
-        player_projections
+        (player_projections, PitchingContext { fip_constants, baseruns })
+    }
+
+    /// The player's age in `year` and the convention it was found with. An age from the roster
+    /// is used over the register's.
+    fn player_age(&self, person: Option<&register::PeopleRegister>, roster_age: Option<f32>,
+                  year: u16) -> Option<(f32, &'static str)>
+    {
+        if let Some(age) = roster_age {
+            return Some((age, "roster"));
+        }
+        let convention = self.parameters.age_convention;
+        person.and_then(|p| p.get_age(year, convention))
+            .map(|age| (age, convention.name()))
+    }
+
+    // Set the batter's age and move the projection along the aging curves from the peak age.
+    fn age_batter(&self, projection: &mut databank::IntBattingProjection,
+                  person: Option<&register::PeopleRegister>, roster_age: Option<f32>, year: u16)
+    {
+        if let Some((age, convention)) = self.player_age(person, roster_age, year) {
+            projection.set_age(age, convention);
+            let peak_age = self.parameters.peak_age as f32;
+            for (stat, factor) in self.parameters.aging.batting_factors(age, peak_age) {
+                projection.adjust(stat, factor);
+            }
+        }
+    }
+
+    // Set the pitcher's age and move the projection along the aging curves from the peak age.
+    fn age_pitcher(&self, projection: &mut databank::IntPitchingProjection,
+                   person: Option<&register::PeopleRegister>, roster_age: Option<f32>, year: u16)
+    {
+        if let Some((age, convention)) = self.player_age(person, roster_age, year) {
+            projection.set_age(age, convention);
+            let peak_age = self.parameters.peak_age as f32;
+            for (stat, factor) in self.parameters.aging.pitching_factors(age, peak_age) {
+                projection.adjust(stat, factor);
+            }
+        }
+    }
+
+    /// Names, handedness and other IDs from the register, when they were asked for and a register
+    /// is loaded.
+    fn player_info(&self, person: Option<&register::PeopleRegister>)
+        -> Option<databank::PlayerInfo>
+    {
+        if !self.player_info || self.people.is_none() {
+            return None;
+        }
+        let field = |value: Option<&str>| value.unwrap_or("").to_string();
+        let info = match person {
+            Some(person) => databank::PlayerInfo {
+                name_first: field(person.name_first()),
+                name_last: field(person.name_last()),
+                bats: field(person.bats()),
+                throws: field(person.throws()),
+                key_mlbam: field(person.key_mlbam()),
+                key_retro: field(person.key_retro()),
+                key_fangraphs: field(person.key_fangraphs()),
+                debut: field(person.debut()),
+            },
+            // Players missing from the register still get the columns, only empty, so every row
+            // has the same columns.
+            None => databank::PlayerInfo::default(),
+        };
+        Some(info)
+    }
+
+    /// The park of the team the player last played for, as of the projected year.
+    fn projected_park(&self, playerid: &str, last_year: Option<u16>, year: u16)
+        -> Option<&park::ParkFactor>
+    {
+        let park_factors = self.park_factors.as_ref()?;
+        let stint = self.players.stints(playerid, last_year?).last()?;
+        park_factors.latest(stint.teamid(), year)
+    }
+
+    // The seasons before `year` with a weight. History before the first season in the data is
+    // simply missing.
+    fn history(&self, year: u16, seasons: usize) -> Option<(u16, u16)> {
+        let start_year = year.saturating_sub(seasons as u16)
+            .max(self.players.first_year());
+        let end_year = year.saturating_sub(1);
+        if start_year > end_year {
+            None
+        }
+        else {
+            Some((start_year, end_year))
+        }
+    }
+
+    /// The league average over the same seasons and weights as everyone else, moved into the
+    /// target era, that replacement level batters start from. Seasons without usable totals are
+    /// left out. Only the mix of stats matters since it is prorated to the regression amount,
+    /// which also renormalizes the weights of the seasons left.
+    fn replacement_batting_mean(&self, year: u16, start_year: u16, weights_map: &[f32],
+                                era_factors: &BTreeMap<u16, Vec<(databank::BattingStat, f32)>>)
+        -> Option<databank::IntBattingProjection>
+    {
+        let mut league_mean = databank::IntBattingProjection::league();
+        for season_year in start_year..year {
+            let league_rate = self.batting_league_totals.get(&season_year)
+                .filter(|league_rate| league_rate.is_finite());
+            if let Some(league_rate) = league_rate {
+                let weight = weights_map[(year - season_year) as usize];
+                let mut season_league = databank::IntBattingProjection::league();
+                season_league.weighted_rate_add(self.parameters.batter_regress, league_rate,
+                                                weight);
+                for (stat, factor) in era_factors.get(&season_year).into_iter().flatten() {
+                    season_league.adjust(*stat, *factor);
+                }
+                league_mean.add(&season_league);
+            }
+        }
+        let finite = databank::BattingStat::ALL.iter()
+            .all(|stat| league_mean.stat(*stat).is_finite());
+        if !finite || league_mean.stat(databank::BattingStat::Pa) <= 0.0 {
+            None
+        }
+        else {
+            Some(league_mean)
+        }
+    }
+
+    /// The same as `replacement_batting_mean` for pitchers.
+    fn replacement_pitching_mean(&self, year: u16, start_year: u16, weights_map: &[f32],
+                                 era_factors: &BTreeMap<u16, Vec<(databank::PitchingStat, f32)>>)
+        -> Option<databank::IntPitchingProjection>
+    {
+        let mut league_mean = databank::IntPitchingProjection::league();
+        for season_year in start_year..year {
+            let league_rate = self.pitching_league_totals.get(&season_year)
+                .filter(|league_rate| league_rate.is_finite());
+            if let Some(league_rate) = league_rate {
+                let weight = weights_map[(year - season_year) as usize];
+                let mut season_league = databank::IntPitchingProjection::league();
+                // Multiply by three because internally using outs rather than IP.
+                season_league.weighted_rate_add(self.parameters.starter_regress * 3, league_rate,
+                                                weight);
+                for (stat, factor) in era_factors.get(&season_year).into_iter().flatten() {
+                    season_league.adjust(*stat, *factor);
+                }
+                league_mean.add(&season_league);
+            }
+        }
+        let finite = databank::PitchingStat::ALL.iter()
+            .all(|stat| league_mean.stat(*stat).is_finite());
+        if !finite || league_mean.stat(databank::PitchingStat::Ip) <= 0.0 {
+            None
+        }
+        else {
+            Some(league_mean)
+        }
+    }
+
+    /// A replacement level batter for a player without any MLB history. It starts from the league
+    /// average, so the reliability is zero.
+    fn replacement_batter(&self, entry: &roster::RosterEntry, year: u16,
+                          league_mean: &databank::IntBattingProjection,
+                          linear_weights: Option<&linearweights::LinearWeights>)
+        -> databank::BattingProjection
+    {
+        let regress_amount = self.parameters.batter_regress;
+        let mut replacement = databank::IntBattingProjection::new_player(&entry.playerid, year);
+        replacement.regress(&league_mean.prorate(regress_amount), &BTreeMap::new());
+        for (stat, factor) in roster::REPLACEMENT_BATTING {
+            replacement.adjust(*stat, *factor);
+        }
+
+        let target = self.depth_chart.as_ref().and_then(|chart| chart.get(&entry.playerid));
+        let projected_pa = target.and_then(|target| target.pa)
+            .unwrap_or(self.parameters.playing_time.batter_baseline);
+        let mut projection = replacement.prorate(projected_pa as u16);
+        let person = self.people.as_ref()
+            .and_then(|people| people.find_by_playerid(&entry.playerid));
+        self.age_batter(&mut projection, person, entry.age, year);
+        let mut projection = projection.finalize(&self.batting_rates, linear_weights);
+        projection.set_team(&entry.stints());
+        if self.two_way {
+            projection.set_two_way(false);
+        }
+        if let Some(info) = self.player_info(person) {
+            projection.set_player_info(info);
+        }
+        projection
+    }
+
+    /// A replacement level pitcher for a player without any MLB history. It starts from the
+    /// league average, so the reliability is zero.
+    fn replacement_pitcher(&self, entry: &roster::RosterEntry, year: u16,
+                           league_mean: &databank::IntPitchingProjection,
+                           fip_constants: Option<&fip::FipConstants>,
+                           baseruns: &baseruns::BaseRuns)
+        -> databank::PitchingProjection
+    {
+        let target = self.depth_chart.as_ref().and_then(|chart| chart.get(&entry.playerid));
+        let is_starter = target.and_then(playingtime::Target::is_starter)
+            .unwrap_or(entry.role == roster::Role::Starter);
+        let (regress_amount, baseline) = if is_starter {
+            (self.parameters.starter_regress, self.parameters.playing_time.starter_baseline)
+        } else {
+            (self.parameters.reliever_regress, self.parameters.playing_time.reliever_baseline)
+        };
+        // Multiply by three because internally using outs rather than IP.
+        let regress_amount = regress_amount * 3;
+        let mut replacement = databank::IntPitchingProjection::new_player(&entry.playerid, year);
+        replacement.regress(&league_mean.prorate(regress_amount), &BTreeMap::new());
+        for (stat, factor) in roster::REPLACEMENT_PITCHING {
+            replacement.adjust(*stat, *factor);
+        }
+
+        let projected_ip = target.and_then(|target| target.ip).unwrap_or(baseline);
+        let mut projection = replacement.prorate((3.0 * projected_ip) as u16);
+        let person = self.people.as_ref()
+            .and_then(|people| people.find_by_playerid(&entry.playerid));
+        self.age_pitcher(&mut projection, person, entry.age, year);
+        let mut projection = projection.finalize(&self.pitching_rates, fip_constants, baseruns,
+                                                 self.parameters.era_blend);
+        projection.set_team(&entry.stints());
+        if self.two_way {
+            projection.set_two_way(false);
+        }
+        if let Some(info) = self.player_info(person) {
+            projection.set_player_info(info);
+        }
+        projection
+    }
+}
+
+fn neutral_batting<'a>(park_factors: Option<&park::ParkFactors>, season: &'a databank::BattingSeason)
+    -> Cow<'a, databank::BattingSeason>
+{
+    match park_factors.and_then(|parks| parks.get(season.teamid(), *season.yearid())) {
+        Some(park) => Cow::Owned(season.neutralized(park)),
+        None => Cow::Borrowed(season),
+    }
+}
+
+fn neutral_pitching<'a>(park_factors: Option<&park::ParkFactors>, season: &'a databank::PitchingSeason)
+    -> Cow<'a, databank::PitchingSeason>
+{
+    match park_factors.and_then(|parks| parks.get(season.teamid(), *season.yearid())) {
+        Some(park) => Cow::Owned(season.neutralized(park)),
+        None => Cow::Borrowed(season),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::databank::{BattingStat, PitchingStat};
+
+    const BATTING_HEADER: &str =
+        "playerID,yearID,stint,teamID,lgID,G,AB,R,H,2B,3B,HR,RBI,SB,CS,BB,SO,IBB,HBP,SH,SF,GIDP";
+    const PITCHING_HEADER: &str =
+        "playerID,yearID,stint,teamID,lgID,W,L,G,GS,SV,IPouts,H,R,ER,HR,BB,SO,IBB,WP,HBP,BK";
+
+    pub(crate) fn parameters() -> Parameters {
+        Parameters {
+            peak_age: 29,
+            age_convention: register::AgeConvention::EndOfYear,
+            aging: aging::AgingModel::marcel(),
+            batter_regress: 1200,
+            starter_regress: 60,
+            reliever_regress: 25,
+            batter_components: BTreeMap::new(),
+            pitcher_components: BTreeMap::new(),
+            batter_weights: vec![5.0, 4.0, 3.0],
+            pitcher_weights: vec![3.0, 2.0, 1.0],
+            era: None,
+            minors_weight: 0.5,
+            playing_time: playingtime::PlayingTime::marcel(),
+            reapply_park: false,
+            mlb_baseline: false,
+            era_blend: 0.5,
+            baseruns: baseruns::BaseRuns::new(),
+            calibrate_baseruns: false,
+        }
+    }
+
+    // Write a file for a single test, named after it so tests running at once don't collide.
+    fn write_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("capuchin-{}-{}.csv", std::process::id(), name));
+        fs::write(&path, contents).expect("Unable to write test file.");
+        path
+    }
+
+    // A batting line with the given AB, H and HR and a tenth of the AB in walks.
+    pub(crate) fn batting_row(playerid: &str, year: u16, teamid: &str, lgid: &str, ab: u16,
+                              h: u16, hr: u16) -> String
+    {
+        format!("{},{},1,{},{},100,{},0,{},0,0,{},0,0,0,{},0,0,0,0,0,0",
+                playerid, year, teamid, lgid, ab, h, hr, ab / 10)
+    }
+
+    // A pitching line over `ipouts` with roughly league average rates.
+    pub(crate) fn pitching_row(playerid: &str, year: u16, g: u8, gs: u8, ipouts: u16)
+        -> String
+    {
+        format!("{},{},1,NYA,AL,0,0,{},{},0,{},{},{},{},{},{},{},0,0,0,0",
+                playerid, year, g, gs, ipouts, ipouts / 3, ipouts / 9, ipouts / 10, ipouts / 40,
+                ipouts / 9, ipouts / 4)
+    }
+
+    pub(crate) fn load(name: &str, parameters: Parameters, batting: &[String],
+                       pitching: &[String]) -> Capuchin
+    {
+        let mut capuchin = Capuchin::new(parameters);
+        if !batting.is_empty() {
+            let path = write_file(&format!("{}-batting", name),
+                                  &format!("{}\n{}\n", BATTING_HEADER, batting.join("\n")));
+            capuchin.load_batting(&path).expect("Unable to load test batting.");
+            fs::remove_file(&path).ok();
+        }
+        if !pitching.is_empty() {
+            let path = write_file(&format!("{}-pitching", name),
+                                  &format!("{}\n{}\n", PITCHING_HEADER, pitching.join("\n")));
+            capuchin.load_pitching(&path).expect("Unable to load test pitching.");
+            fs::remove_file(&path).ok();
+        }
+        capuchin
+    }
+
+    // Ten full seasons for each league, where the AL hits far more home runs.
+    fn league_rows(year: u16) -> Vec<String> {
+        let mut rows = Vec::new();
+        for idx in 0..10 {
+            rows.push(batting_row(&format!("al{:02}", idx), year, "NYA", "AL", 500, 150, 40));
+            rows.push(batting_row(&format!("nl{:02}", idx), year, "NYN", "NL", 500, 120, 5));
+        }
+        rows
+    }
+
+    fn batter<'a>(projections: &'a [databank::BattingProjection], playerid: &str)
+        -> &'a databank::BattingProjection
+    {
+        projections.iter()
+            .find(|projection| projection.playerid() == playerid)
+            .expect("Expected a projection for the batter.")
+    }
+
+    fn pitcher<'a>(projections: &'a [databank::PitchingProjection], playerid: &str)
+        -> &'a databank::PitchingProjection
+    {
+        projections.iter()
+            .find(|projection| projection.playerid() == playerid)
+            .expect("Expected a projection for the pitcher.")
+    }
+
+    fn hr_rate(projections: &[databank::BattingProjection], playerid: &str) -> f32 {
+        let projection = batter(projections, playerid);
+        projection.stat(BattingStat::Hr) / projection.stat(BattingStat::Pa)
+    }
+
+    #[test]
+    fn regress_toward_own_league() {
+        let mut rows = league_rows(2014);
+        // Players with a single out are almost entirely the league mean.
+        rows.push(batting_row("alone", 2014, "NYA", "AL", 1, 0, 0));
+        rows.push(batting_row("unknown", 2014, "XXX", "", 1, 0, 0));
+        // Plenty of PA so rounding the projection doesn't hide its rates.
+        let mut parameters = parameters();
+        parameters.playing_time.batter_baseline = 10000.0;
+        let mut capuchin = load("split-totals", parameters, &rows, &[]);
+        let (projections, _) = capuchin.batting_projection(2015);
+
+        let al_rate = 400.0 / 5501.0;
+        let mlb_rate = 450.0 / 11002.0;
+        assert!((hr_rate(&projections, "alone") / al_rate - 1.0).abs() < 0.01);
+        assert!((hr_rate(&projections, "unknown") / mlb_rate - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn marcel_playing_time() {
+        let mut batting = Vec::new();
+        for (year, ab) in &[(2012, 400), (2013, 300), (2014, 500)] {
+            batting.extend(league_rows(*year));
+            batting.push(batting_row("regular", *year, "NYA", "AL", *ab, *ab / 4, 10));
+        }
+        let pitching = vec![
+            pitching_row("starter", 2013, 25, 25, 450),
+            pitching_row("starter", 2014, 30, 30, 540),
+            pitching_row("reliever", 2014, 60, 0, 180),
+        ];
+        let mut capuchin = load("marcel-playing-time", parameters(), &batting, &pitching);
+
+        let (batters, _) = capuchin.batting_projection(2015);
+        // 200 PA plus half of 550 and a tenth of 330.
+        assert_eq!(batter(&batters, "regular").stat(BattingStat::Pa), 508.0);
+        let (pitchers, _) = capuchin.pitching_projection(2015);
+        // 60 IP plus half of 180 and a tenth of 150, 25 IP plus half of 60.
+        assert_eq!(pitcher(&pitchers, "starter").stat(PitchingStat::Ip), 165.0);
+        assert_eq!(pitcher(&pitchers, "reliever").stat(PitchingStat::Ip), 55.0);
+    }
+
+    #[test]
+    fn depth_chart_playing_time() {
+        let mut batting = league_rows(2014);
+        batting.push(batting_row("regular", 2014, "NYA", "AL", 500, 125, 10));
+        let pitching = vec![
+            pitching_row("starter", 2014, 30, 30, 540),
+            pitching_row("reliever", 2014, 60, 0, 180),
+        ];
+        let mut capuchin = load("depth-chart", parameters(), &batting, &pitching);
+        let path = write_file("depth-chart-targets",
+                              "playerID,PA,IP,GS\nregular,620,,\nreliever,,120,22\n");
+        let chart = playingtime::DepthChart::from_path(&path)
+            .expect("Unable to load test depth chart.");
+        fs::remove_file(&path).ok();
+        capuchin.load_depth_chart(chart);
+
+        let (batters, _) = capuchin.batting_projection(2015);
+        assert_eq!(batter(&batters, "regular").stat(BattingStat::Pa), 620.0);
+        // Players missing from the chart keep the formula.
+        assert_eq!(batter(&batters, "al00").stat(BattingStat::Pa), 475.0);
+        let (pitchers, _) = capuchin.pitching_projection(2015);
+        assert_eq!(pitcher(&pitchers, "reliever").stat(PitchingStat::Ip), 120.0);
+        assert_eq!(pitcher(&pitchers, "starter").stat(PitchingStat::Ip), 150.0);
+    }
+
+    #[test]
+    fn project_past_the_data() {
+        let mut batting = Vec::new();
+        let mut pitching = Vec::new();
+        for year in 2013..=2014 {
+            batting.extend(league_rows(year));
+            pitching.push(pitching_row("starter", year, 30, 30, 540));
+            pitching.push(pitching_row("reliever", year, 60, 0, 180));
+        }
+        let mut capuchin = load("past-the-data", parameters(), &batting, &pitching);
+
+        let (batters, context) = capuchin.batting_projection(2016);
+        assert!(capuchin.batting_league_rates(2015).is_none());
+        assert!(context.linear_weights.is_some());
+        let projection = batter(&batters, "al00");
+        for stat in &BattingStat::ALL {
+            assert!(projection.stat(*stat).is_finite());
+        }
+        assert!(projection.stat(BattingStat::H) > 0.0);
+        assert!(projection.stat(BattingStat::Hr) > 0.0);
+
+        let (pitchers, context) = capuchin.pitching_projection(2016);
+        assert!(capuchin.pitching_league_rates(2015).is_none());
+        assert!(context.fip_constants.is_some());
+        let projection = pitcher(&pitchers, "starter");
+        for stat in &PitchingStat::ALL {
+            assert!(projection.stat(*stat).is_finite());
+        }
+        assert!(projection.stat(PitchingStat::So) > 0.0);
+        assert!(projection.era().is_finite());
+    }
+
+    #[test]
+    fn replacement_prior() {
+        let mut batting = Vec::new();
+        let mut pitching = Vec::new();
+        for year in 2013..=2014 {
+            batting.extend(league_rows(year));
+            pitching.push(pitching_row("starter", year, 30, 30, 540));
+            pitching.push(pitching_row("reliever", year, 60, 0, 180));
+        }
+        let mut capuchin = load("replacement-prior", parameters(), &batting, &pitching);
+        let path = write_file("replacement-prior-roster",
+                              "playerID,role,teamID,lgID\nrookie01,batter,SEA,AL\n\
+                               rookie02,starter,SEA,AL\n");
+        capuchin.load_roster(roster::Roster::from_path(&path).expect("Unable to load roster."));
+        fs::remove_file(&path).ok();
+
+        // 2015 has no data, which must not spoil the league average the rookies start from.
+        let (batters, _) = capuchin.batting_projection(2016);
+        let rookie = batter(&batters, "rookie01");
+        for stat in &BattingStat::ALL {
+            assert!(rookie.stat(*stat).is_finite());
+        }
+        assert_eq!(rookie.stat(BattingStat::Pa), 200.0);
+        // Below the league's average, but not by much.
+        let rookie_rate = hr_rate(&batters, "rookie01");
+        let league_rate = 450.0 / 11000.0;
+        assert!(rookie_rate < league_rate && rookie_rate > 0.5 * league_rate);
+
+        let (pitchers, _) = capuchin.pitching_projection(2016);
+        let rookie = pitcher(&pitchers, "rookie02");
+        for stat in &PitchingStat::ALL {
+            assert!(rookie.stat(*stat).is_finite());
+        }
+        assert_eq!(rookie.stat(PitchingStat::Ip), 60.0);
+        assert!(rookie.stat(PitchingStat::So) > 0.0);
+        assert!(rookie.era().is_finite());
     }
 }
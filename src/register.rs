@@ -1,43 +1,91 @@
 use std::collections::HashMap;
 use std::io::Read;
+use std::str::FromStr;
 
-use csv;
 use serde::Deserialize;
 
 
+// Days in each month of a non-leap year.
+const DAYS_IN_MONTH: [u8; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+
+/// Which date in the season a player's age is taken from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AgeConvention {
+    /// Age at the end of the year, what Tangotiger uses for Marcel.
+    EndOfYear,
+    /// Age on June 30, MLB's season age.
+    Mlb,
+    /// Age on July 1, Baseball-Reference's season age.
+    BaseballReference,
+    /// Fractional age on July 1.
+    Exact,
+}
+
+/// Layout of the register file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegisterFormat {
+    /// The Chadwick Bureau register, keyed by `key_bbref`.
+    Chadwick,
+    /// Lahman's People.csv, keyed by `playerID`.
+    Lahman,
+}
+
 pub struct People {
     people: Vec<PeopleRegister>,
-    bbref_idx: HashMap<String, usize>,
+    playerid_idx: HashMap<String, usize>,
 }
 
-#[derive(Deserialize)]
+/// Biographical details of a single player, whichever register they came from.
 pub struct PeopleRegister {
+    playerid: Option<String>,
+    key_mlbam: Option<String>,
+    key_retro: Option<String>,
+    key_fangraphs: Option<String>,
+    birth_year: Option<u16>,
+    birth_month: Option<u8>,
+    birth_day: Option<u8>,
+    name_first: Option<String>,
+    name_last: Option<String>,
+    bats: Option<String>,
+    throws: Option<String>,
+    debut: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawChadwickPerson {
     /*
     key_person: String,
     */
+    // Never read, but requiring it rejects files that are not the register.
+    #[allow(dead_code)]
     key_uuid: String,
-    //key_mlbam: Option<String>,
-    //key_retro: Option<String>,
+    key_mlbam: Option<String>,
+    key_retro: Option<String>,
     key_bbref: Option<String>,
     /*
     key_bbref_minors: Option<String>,
+    */
     key_fangraphs: Option<String>,
+    /*
     key_npb: Option<String>,
     key_sr_nfl: Option<String>,
     key_sr_nba: Option<String>,
     key_sr_nhl: Option<String>,
     key_findagrave: Option<String>,
+    */
     name_last: Option<String>,
     name_first: Option<String>,
+    /*
     name_given: Option<String>,
     name_suffix: Option<String>,
     name_matrilineal: Option<String>,
     name_nick: Option<String>,
     */
     birth_year: Option<u16>,
-    /*
     birth_month: Option<u8>,
     birth_day: Option<u8>,
+    /*
     death_year: Option<String>,
     death_month: Option<String>,
     death_day: Option<String>,
@@ -60,34 +108,263 @@ pub struct PeopleRegister {
     */
 }
 
+#[derive(Deserialize)]
+struct RawLahmanPerson {
+    #[serde(rename = "playerID")]
+    playerid: Option<String>,
+    #[serde(rename = "birthYear")]
+    birth_year: Option<u16>,
+    #[serde(rename = "birthMonth")]
+    birth_month: Option<u8>,
+    #[serde(rename = "birthDay")]
+    birth_day: Option<u8>,
+    #[serde(rename = "nameFirst")]
+    name_first: Option<String>,
+    #[serde(rename = "nameLast")]
+    name_last: Option<String>,
+    bats: Option<String>,
+    throws: Option<String>,
+    debut: Option<String>,
+    #[serde(rename = "retroID")]
+    retroid: Option<String>,
+}
+
 impl People {
-    pub fn from_register<R: Read>(register: R) -> Result<Self, csv::Error> {
+    /// Load a register. Without a format it is detected from the header row.
+    pub fn from_register<R: Read>(register: R, format: Option<RegisterFormat>) -> Result<Self, csv::Error> {
         let mut reader = csv::Reader::from_reader(register);
+        let format = match format {
+            Some(format) => format,
+            None => RegisterFormat::detect(reader.headers()?),
+        };
         let mut people = Vec::new();
-        let mut bbref_idx = HashMap::new();
+        let mut playerid_idx = HashMap::new();
 
-        for result in reader.deserialize() {
-            let person: PeopleRegister = result?;
+        let mut add_person = |person: PeopleRegister| {
             let idx = people.len();
-            if let Some(ref bbrefid) = person.key_bbref {
-                bbref_idx.insert(bbrefid.clone(), idx);
+            if let Some(ref playerid) = person.playerid {
+                playerid_idx.insert(playerid.clone(), idx);
             }
             people.push(person);
+        };
+        match format {
+            RegisterFormat::Chadwick => {
+                for result in reader.deserialize() {
+                    let person: RawChadwickPerson = result?;
+                    add_person(person.into());
+                }
+            }
+            RegisterFormat::Lahman => {
+                for result in reader.deserialize() {
+                    let person: RawLahmanPerson = result?;
+                    add_person(person.into());
+                }
+            }
         }
 
-        let people = People { people, bbref_idx };
+        let people = People { people, playerid_idx };
         Ok(people)
     }
 
-    pub fn find_by_bbref(&self, key_bbref: &str) -> Option<&PeopleRegister> {
-        self.bbref_idx.get(key_bbref).and_then(|idx| self.people.get(*idx))
+    /// Find a player by the Lahman `playerID`. The Chadwick register's `key_bbref` is the same ID.
+    pub fn find_by_playerid(&self, playerid: &str) -> Option<&PeopleRegister> {
+        self.playerid_idx.get(playerid).and_then(|idx| self.people.get(*idx))
+    }
+}
+
+impl RegisterFormat {
+    // Lahman's People.csv is the only one with a playerID column.
+    fn detect(headers: &csv::StringRecord) -> Self {
+        if headers.iter().any(|header| header == "playerID") {
+            RegisterFormat::Lahman
+        }
+        else {
+            RegisterFormat::Chadwick
+        }
+    }
+}
+
+impl FromStr for RegisterFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "chadwick" => Ok(RegisterFormat::Chadwick),
+            "lahman" => Ok(RegisterFormat::Lahman),
+            _ => Err(format!("Unknown register format '{}'", format)),
+        }
+    }
+}
+
+impl From<RawChadwickPerson> for PeopleRegister {
+    fn from(person: RawChadwickPerson) -> PeopleRegister {
+        PeopleRegister {
+            playerid: person.key_bbref,
+            key_mlbam: person.key_mlbam,
+            key_retro: person.key_retro,
+            key_fangraphs: person.key_fangraphs,
+            birth_year: person.birth_year,
+            birth_month: person.birth_month,
+            birth_day: person.birth_day,
+            name_first: person.name_first,
+            name_last: person.name_last,
+            bats: None,
+            throws: None,
+            debut: None,
+        }
+    }
+}
+
+impl From<RawLahmanPerson> for PeopleRegister {
+    fn from(person: RawLahmanPerson) -> PeopleRegister {
+        PeopleRegister {
+            playerid: person.playerid,
+            // Lahman only cross-references Retrosheet.
+            key_mlbam: None,
+            key_retro: person.retroid,
+            key_fangraphs: None,
+            birth_year: person.birth_year,
+            birth_month: person.birth_month,
+            birth_day: person.birth_day,
+            name_first: person.name_first,
+            name_last: person.name_last,
+            bats: person.bats,
+            throws: person.throws,
+            debut: person.debut,
+        }
+    }
+}
+
+impl AgeConvention {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgeConvention::EndOfYear => "end-of-year",
+            AgeConvention::Mlb => "mlb",
+            AgeConvention::BaseballReference => "bbref",
+            AgeConvention::Exact => "exact",
+        }
+    }
+
+    // Month and day the age is taken on, or None for the end of the year.
+    fn reference_date(&self) -> Option<(u8, u8)> {
+        match self {
+            AgeConvention::EndOfYear => None,
+            AgeConvention::Mlb => Some((6, 30)),
+            AgeConvention::BaseballReference | AgeConvention::Exact => Some((7, 1)),
+        }
+    }
+}
+
+impl FromStr for AgeConvention {
+    type Err = String;
+
+    fn from_str(convention: &str) -> Result<Self, Self::Err> {
+        match convention {
+            "end-of-year" => Ok(AgeConvention::EndOfYear),
+            "mlb" => Ok(AgeConvention::Mlb),
+            "bbref" => Ok(AgeConvention::BaseballReference),
+            "exact" => Ok(AgeConvention::Exact),
+            _ => Err(format!("Unknown age convention '{}'", convention)),
+        }
     }
 }
 
 impl PeopleRegister {
+    pub fn name_first(&self) -> Option<&str> {
+        self.name_first.as_deref()
+    }
+
+    pub fn name_last(&self) -> Option<&str> {
+        self.name_last.as_deref()
+    }
+
+    pub fn key_mlbam(&self) -> Option<&str> {
+        self.key_mlbam.as_deref()
+    }
+
+    pub fn key_retro(&self) -> Option<&str> {
+        self.key_retro.as_deref()
+    }
+
+    pub fn key_fangraphs(&self) -> Option<&str> {
+        self.key_fangraphs.as_deref()
+    }
+
+    pub fn bats(&self) -> Option<&str> {
+        self.bats.as_deref()
+    }
+
+    pub fn throws(&self) -> Option<&str> {
+        self.throws.as_deref()
+    }
+
+    /// Date of the player's MLB debut as written in the register, only in Lahman's People.csv.
+    pub fn debut(&self) -> Option<&str> {
+        self.debut.as_deref()
+    }
+
     // MLB's season age is the age of a player on June 30. Tangotiger thinks that is silly so uses
-    // the simpler method of whatever their age is by the end of the year.
-    pub fn get_age(&self, season: u16) -> Option<u8> {
-        self.birth_year.and_then(|birth| Some((season - birth) as u8))
+    // the simpler method of whatever their age is by the end of the year. Players without a birth
+    // month and day fall back to the end of year age, or a July 1 birthday for exact ages.
+    pub fn get_age(&self, season: u16, convention: AgeConvention) -> Option<f32> {
+        let birth_year = self.birth_year?;
+        let years = season as f32 - birth_year as f32;
+        let (ref_month, ref_day) = match convention.reference_date() {
+            Some(date) => date,
+            None => return Some(years),
+        };
+
+        match (convention, self.birth_month, self.birth_day) {
+            (AgeConvention::Exact, month, day) => {
+                let (month, day) = (month.unwrap_or(7), day.unwrap_or(1));
+                let birth_year = birth_year as i32;
+                let reference = days_from_civil(season as i32, ref_month, ref_day);
+                // Whole years up to the last birthday, then the share of the way to the next.
+                let mut last_year = season as i32;
+                if birthday(last_year, month, day) > reference {
+                    last_year -= 1;
+                }
+                let last = birthday(last_year, month, day);
+                let next = birthday(last_year + 1, month, day);
+                Some((last_year - birth_year) as f32 + (reference - last) as f32 / (next - last) as f32)
+            }
+            (_, Some(month), Some(day)) => {
+                // Not a year older until the birthday has passed.
+                if (month, day) > (ref_month, ref_day) {
+                    Some(years - 1.0)
+                }
+                else {
+                    Some(years)
+                }
+            }
+            _ => Some(years),
+        }
     }
 }
+
+// The day a birthday falls on in `year`. February 29 birthdays are on February 28 in other years.
+fn birthday(year: i32, month: u8, day: u8) -> i32 {
+    let month = month.clamp(1, 12);
+    days_from_civil(year, month, day.clamp(1, days_in_month(year, month)))
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    if month == 2 && leap {
+        29
+    }
+    else {
+        DAYS_IN_MONTH[(month - 1) as usize]
+    }
+}
+
+// Days from 1970-01-01 to a date in the Gregorian calendar.
+fn days_from_civil(year: i32, month: u8, day: u8) -> i32 {
+    let (month, day) = (month as i32, day as i32);
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
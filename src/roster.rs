@@ -0,0 +1,134 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::databank::{BattingStat, PitchingStat, Stint};
+
+
+// How a replacement level player compares to the league average, per PA for batters and per out
+// for pitchers. Stats not listed stay at the league average.
+pub const REPLACEMENT_BATTING: &[(BattingStat, f32)] = &[
+    (BattingStat::R, 0.85),
+    (BattingStat::H, 0.93),
+    (BattingStat::Double, 0.90),
+    (BattingStat::Triple, 0.90),
+    (BattingStat::Hr, 0.80),
+    (BattingStat::Rbi, 0.85),
+    (BattingStat::Bb, 0.85),
+    (BattingStat::Ibb, 0.70),
+    (BattingStat::So, 1.10),
+];
+pub const REPLACEMENT_PITCHING: &[(PitchingStat, f32)] = &[
+    (PitchingStat::W, 0.85),
+    (PitchingStat::L, 1.10),
+    (PitchingStat::Sv, 0.50),
+    (PitchingStat::H, 1.06),
+    (PitchingStat::R, 1.12),
+    (PitchingStat::Er, 1.12),
+    (PitchingStat::Hr, 1.15),
+    (PitchingStat::So, 0.88),
+    (PitchingStat::Bb, 1.12),
+    (PitchingStat::Hbp, 1.10),
+];
+
+
+/// What a rostered player is projected as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Batter,
+    Starter,
+    Reliever,
+}
+
+/// A player expected to play, whether or not they have played in the majors.
+#[derive(Debug)]
+pub struct RosterEntry {
+    pub playerid: String,
+    /// Age to use instead of the register's.
+    pub age: Option<f32>,
+    pub role: Role,
+    /// Team and league the player is rostered with.
+    pub teamid: Option<String>,
+    pub lgid: Option<String>,
+}
+
+/// Players to project even without any MLB history.
+pub struct Roster {
+    entries: Vec<RosterEntry>,
+}
+
+#[derive(Deserialize)]
+struct RawRosterEntry {
+    playerid: String,
+    #[serde(default)]
+    age: Option<f32>,
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    teamid: Option<String>,
+    #[serde(default)]
+    lgid: Option<String>,
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role.to_lowercase().as_str() {
+            "batter" | "b" => Ok(Role::Batter),
+            "starter" | "sp" => Ok(Role::Starter),
+            "reliever" | "rp" | "pitcher" | "p" => Ok(Role::Reliever),
+            _ => Err(format!("Unknown role '{}', expected batter, starter or reliever", role)),
+        }
+    }
+}
+
+impl RosterEntry {
+    /// The roster's team as a single stint, or none without a team.
+    pub fn stints(&self) -> Vec<Stint> {
+        match self.teamid {
+            Some(ref teamid) => vec![Stint::new(1, teamid, self.lgid.as_deref().unwrap_or(""))],
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Roster {
+    /// Load a CSV file with a playerID column and optional age, role, teamID and lgID columns.
+    /// Players without a role are projected as batters.
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let mut reader = csv::Reader::from_path(path).map_err(|err| err.to_string())?;
+        let headers = reader.headers()
+            .map_err(|err| err.to_string())?
+            .iter()
+            .map(str::to_lowercase)
+            .collect();
+        reader.set_headers(headers);
+
+        let mut entries = Vec::new();
+        for record in reader.deserialize() {
+            let record: RawRosterEntry = record.map_err(|err| err.to_string())?;
+            let role = match record.role {
+                Some(ref role) if !role.trim().is_empty() => Role::from_str(role.trim())?,
+                _ => Role::Batter,
+            };
+            entries.push(RosterEntry {
+                playerid: record.playerid,
+                age: record.age,
+                role,
+                teamid: record.teamid.filter(|teamid| !teamid.trim().is_empty()),
+                lgid: record.lgid.filter(|lgid| !lgid.trim().is_empty()),
+            });
+        }
+        Ok(Roster { entries })
+    }
+
+    pub fn batters(&self) -> impl Iterator<Item = &RosterEntry> {
+        self.entries.iter().filter(|entry| entry.role == Role::Batter)
+    }
+
+    pub fn pitchers(&self) -> impl Iterator<Item = &RosterEntry> {
+        self.entries.iter().filter(|entry| entry.role != Role::Batter)
+    }
+}
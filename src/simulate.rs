@@ -0,0 +1,418 @@
+use std::f64::consts::PI;
+use std::thread;
+
+use crate::databank::{BattingProjection, BattingStat, PitchingProjection, PitchingStat};
+use crate::uncertainty::Percentiles;
+
+
+// Spread of a player's playing time as a share of the projection, covering injuries and changes
+// in role.
+const PLAYING_TIME_CV: f64 = 0.15;
+// Binomial and Poisson draws with at least this variance use a normal approximation instead of
+// counting up one event at a time.
+const NORMAL_APPROXIMATION: f64 = 10.0;
+
+// Columns written for each simulated batter, in order.
+const BATTING_COLUMNS: &[&str] = &[
+    "pa", "ab", "r", "h", "2B", "3B", "hr", "rbi", "sb", "cs", "bb", "so", "hbp", "avg", "obp",
+    "slg",
+];
+// Columns written for each simulated pitcher, in order.
+const PITCHING_COLUMNS: &[&str] = &[
+    "ip", "w", "l", "sv", "h", "r", "er", "hr", "so", "bb", "hbp", "era", "whip",
+];
+
+
+/// SplitMix64, a small and fast generator so simulations can be repeated from a seed.
+pub struct Rng {
+    state: u64,
+    // Box-Muller makes normal draws in pairs, so the second is kept for the next call.
+    spare_normal: Option<f64>,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed, spare_normal: None }
+    }
+
+    /// A separate stream for each player, mixed from the seed and the player's position.
+    pub fn for_player(seed: u64, player: u64) -> Self {
+        Rng::new(Rng::new(seed.wrapping_add(player)).next_u64())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1).
+    fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Standard normal by Box-Muller.
+    fn normal(&mut self) -> f64 {
+        if let Some(normal) = self.spare_normal.take() {
+            return normal;
+        }
+        let u1 = 1.0 - self.uniform();
+        let u2 = self.uniform();
+        let radius = (-2.0 * u1.ln()).sqrt();
+        let (sin, cos) = (2.0 * PI * u2).sin_cos();
+        self.spare_normal = Some(radius * sin);
+        radius * cos
+    }
+
+    fn binomial(&mut self, n: u32, p: f64) -> u32 {
+        if n == 0 || p <= 0.0 {
+            return 0;
+        }
+        if p >= 1.0 {
+            return n;
+        }
+        if p > 0.5 {
+            return n - self.binomial(n, 1.0 - p);
+        }
+
+        let mean = n as f64 * p;
+        let variance = mean * (1.0 - p);
+        if variance >= NORMAL_APPROXIMATION {
+            let draw = (mean + variance.sqrt() * self.normal()).round();
+            return draw.max(0.0).min(n as f64) as u32;
+        }
+
+        // Inversion: walk up the probability of each count until the uniform draw is covered.
+        let q = 1.0 - p;
+        let s = p / q;
+        let a = (n + 1) as f64 * s;
+        let mut r = q.powi(n as i32);
+        let mut u = self.uniform();
+        let mut x = 0;
+        while u > r && x < n {
+            u -= r;
+            x += 1;
+            r *= a / x as f64 - s;
+        }
+        x
+    }
+
+    fn poisson(&mut self, mean: f64) -> u32 {
+        if mean <= 0.0 {
+            return 0;
+        }
+        if mean >= NORMAL_APPROXIMATION {
+            let draw = (mean + mean.sqrt() * self.normal()).round();
+            return draw.max(0.0) as u32;
+        }
+
+        let mut p = (-mean).exp();
+        let mut cumulative = p;
+        let u = self.uniform();
+        let mut x = 0;
+        while u > cumulative && p > 0.0 {
+            x += 1;
+            p *= mean / x as f64;
+            cumulative += p;
+        }
+        x
+    }
+
+    // A season's PA or outs, varied around the projection.
+    fn playing_time(&mut self, projected: f64) -> u32 {
+        let draw = projected * (1.0 + PLAYING_TIME_CV * self.normal());
+        draw.round().max(0.0) as u32
+    }
+
+    // The player's true rate for a season, drawn around the projected rate with the variance
+    // left after `sample` PA or outs of evidence.
+    fn talent(&mut self, rate: f64, variance: f64, sample: f64) -> f64 {
+        if sample <= 0.0 {
+            return rate;
+        }
+        (rate + (variance / sample).sqrt() * self.normal()).max(0.0)
+    }
+}
+
+/// Simulate `simulations` seasons for every batter and summarize each stat's distribution.
+///
+/// Each season draws PA around the projection, then a true rate for every event from the
+/// uncertainty left after regression, then the events themselves. Outcomes of a PA are drawn
+/// together so they never add up to more than the PA.
+pub fn simulate_batting(projections: &[BattingProjection], year: u16, simulations: u32, seed: u64)
+    -> Vec<Percentiles>
+{
+    in_parallel(projections, seed,
+                |projection, rng| simulate_batter(projection, year, simulations, rng))
+}
+
+/// Simulate `simulations` seasons for every pitcher and summarize each stat's distribution.
+///
+/// Outs are drawn around the projection and every event is a Poisson draw per out. Earned runs
+/// follow the projected ERA, so the simulated ERA centers on it, and home runs are a share of the
+/// hits allowed.
+pub fn simulate_pitching(projections: &[PitchingProjection], year: u16, simulations: u32,
+                         seed: u64) -> Vec<Percentiles>
+{
+    in_parallel(projections, seed,
+                |projection, rng| simulate_pitcher(projection, year, simulations, rng))
+}
+
+fn simulate_batter(projection: &BattingProjection, year: u16, simulations: u32, rng: &mut Rng)
+    -> Vec<Percentiles>
+{
+    const OUTCOMES: [BattingStat; 8] = [
+        BattingStat::Bb, BattingStat::Hbp, BattingStat::So, BattingStat::Double,
+        BattingStat::Triple, BattingStat::Hr, BattingStat::Sf, BattingStat::Sh,
+    ];
+    const EXTRAS: [BattingStat; 4] = [
+        BattingStat::R, BattingStat::Rbi, BattingStat::Sb, BattingStat::Cs,
+    ];
+
+    let pa = projection.stat(BattingStat::Pa) as f64;
+    if pa <= 0.0 {
+        return Vec::new();
+    }
+    let sample = projection.sample() as f64;
+    let rate = |stat: BattingStat| projection.stat(stat) as f64 / pa;
+    let singles = (projection.stat(BattingStat::H) - projection.stat(BattingStat::Double)
+                   - projection.stat(BattingStat::Triple) - projection.stat(BattingStat::Hr))
+        .max(0.0) as f64 / pa;
+    // Singles go last so the outcomes are listed in the same order as OUTCOMES.
+    let mut outcome_rates: Vec<f64> = OUTCOMES.iter().map(|stat| rate(*stat)).collect();
+    outcome_rates.push(singles);
+    let extra_rates: Vec<f64> = EXTRAS.iter().map(|stat| rate(*stat)).collect();
+
+    let mut draws = vec![Vec::with_capacity(simulations as usize); BATTING_COLUMNS.len()];
+    let mut outcomes = [0; OUTCOMES.len() + 1];
+    for _ in 0..simulations {
+        let season_pa = rng.playing_time(pa);
+
+        let mut talent = [0.0; OUTCOMES.len() + 1];
+        for (talent, rate) in talent.iter_mut().zip(outcome_rates.iter()) {
+            *talent = rng.talent(*rate, rate * (1.0 - rate.min(1.0)), sample);
+        }
+        let total: f64 = talent.iter().sum();
+        if total > 1.0 {
+            talent.iter_mut().for_each(|rate| *rate /= total);
+        }
+
+        // Draw the outcomes one at a time out of the PA that are left.
+        let mut remaining = season_pa;
+        let mut remaining_rate = 1.0;
+        for (outcome, rate) in outcomes.iter_mut().zip(talent.iter()) {
+            let share = if remaining_rate > 0.0 { rate / remaining_rate } else { 0.0 };
+            *outcome = rng.binomial(remaining, share);
+            remaining -= *outcome;
+            remaining_rate -= rate;
+        }
+        let [bb, hbp, so, double, triple, hr, sf, sh, single] = outcomes;
+        let mut extras = [0; EXTRAS.len()];
+        for (extra, rate) in extras.iter_mut().zip(extra_rates.iter()) {
+            let rate = rng.talent(*rate, rate * (1.0 - rate.min(1.0)), sample);
+            *extra = rng.binomial(season_pa, rate);
+        }
+
+        let ab = season_pa - (bb + hbp + sf + sh);
+        let h = single + double + triple + hr;
+        let total_bases = single + 2 * double + 3 * triple + 4 * hr;
+        let season = [
+            Some(season_pa as f32), Some(ab as f32), Some(extras[0] as f32), Some(h as f32),
+            Some(double as f32), Some(triple as f32), Some(hr as f32), Some(extras[1] as f32),
+            Some(extras[2] as f32), Some(extras[3] as f32), Some(bb as f32), Some(so as f32),
+            Some(hbp as f32), ratio(h, ab), ratio(h + bb + hbp, ab + bb + hbp + sf),
+            ratio(total_bases, ab),
+        ];
+        record(&mut draws, &season);
+    }
+    summarize(projection.playerid(), year, BATTING_COLUMNS, &mut draws)
+}
+
+fn simulate_pitcher(projection: &PitchingProjection, year: u16, simulations: u32, rng: &mut Rng)
+    -> Vec<Percentiles>
+{
+    let outs = projection.stat(PitchingStat::Ip) as f64 * 3.0;
+    if outs <= 0.0 {
+        return Vec::new();
+    }
+    let sample = projection.sample() as f64;
+    let rate = |stat: PitchingStat| projection.stat(stat) as f64 / outs;
+    let er_rate = projection.era() as f64 / 27.0;
+    let unearned_rate = ((projection.stat(PitchingStat::R) - projection.stat(PitchingStat::Er))
+                         .max(0.0)) as f64 / outs;
+    let h = projection.stat(PitchingStat::H) as f64;
+    let hr_share = if h > 0.0 {
+        (projection.stat(PitchingStat::Hr) as f64 / h).min(1.0)
+    }
+    else {
+        0.0
+    };
+    let rates = [
+        rate(PitchingStat::W), rate(PitchingStat::L), rate(PitchingStat::Sv),
+        rate(PitchingStat::H), er_rate, unearned_rate, rate(PitchingStat::So),
+        rate(PitchingStat::Bb), rate(PitchingStat::Hbp),
+    ];
+
+    let mut draws = vec![Vec::with_capacity(simulations as usize); PITCHING_COLUMNS.len()];
+    for _ in 0..simulations {
+        let season_outs = rng.playing_time(outs);
+        let mut events = [0; 9];
+        for (event, rate) in events.iter_mut().zip(rates.iter()) {
+            let rate = rng.talent(*rate, *rate, sample);
+            *event = rng.poisson(season_outs as f64 * rate);
+        }
+        let [w, l, sv, h, er, unearned, so, bb, hbp] = events;
+        // Every strikeout is an out.
+        let so = so.min(season_outs);
+        let hr = rng.binomial(h, hr_share);
+        let ip = season_outs as f32 / 3.0;
+        let (era, whip) = if season_outs > 0 {
+            (Some(27.0 * er as f32 / season_outs as f32),
+             Some(3.0 * (h + bb) as f32 / season_outs as f32))
+        }
+        else {
+            (None, None)
+        };
+
+        let season = [
+            Some(ip), Some(w as f32), Some(l as f32), Some(sv as f32), Some(h as f32),
+            Some((er + unearned) as f32), Some(er as f32), Some(hr as f32), Some(so as f32),
+            Some(bb as f32), Some(hbp as f32), era, whip,
+        ];
+        record(&mut draws, &season);
+    }
+    summarize(projection.playerid(), year, PITCHING_COLUMNS, &mut draws)
+}
+
+// Run `simulate` for every player, split across threads. Each player gets their own stream of
+// draws from the seed so the results do not depend on the number of threads.
+fn in_parallel<P, F>(projections: &[P], seed: u64, simulate: F) -> Vec<Percentiles>
+    where P: Sync,
+          F: Fn(&P, &mut Rng) -> Vec<Percentiles> + Sync
+{
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size = projections.len().div_ceil(threads).max(1);
+    let simulate = &simulate;
+    thread::scope(|scope| {
+        let handles: Vec<_> = projections.chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk, players)| scope.spawn(move || {
+                players.iter()
+                    .enumerate()
+                    .flat_map(|(index, projection)| {
+                        let player = (chunk * chunk_size + index) as u64;
+                        simulate(projection, &mut Rng::for_player(seed, player))
+                    })
+                    .collect::<Vec<_>>()
+            }))
+            .collect();
+        handles.into_iter()
+            .flat_map(|handle| handle.join().expect("Simulation thread panicked"))
+            .collect()
+    })
+}
+
+fn ratio(numerator: u32, denominator: u32) -> Option<f32> {
+    if denominator > 0 {
+        Some(numerator as f32 / denominator as f32)
+    }
+    else {
+        None
+    }
+}
+
+// Add a season to the draws. Rates without a denominator that season are left out.
+fn record(draws: &mut [Vec<f32>], season: &[Option<f32>]) {
+    for (column, value) in draws.iter_mut().zip(season.iter()) {
+        if let Some(value) = value {
+            column.push(*value);
+        }
+    }
+}
+
+fn summarize(playerid: &str, year: u16, columns: &[&'static str], draws: &mut [Vec<f32>])
+    -> Vec<Percentiles>
+{
+    columns.iter()
+        .zip(draws.iter_mut())
+        .filter(|(_, column_draws)| !column_draws.is_empty())
+        .map(|(column, column_draws)| Percentiles::from_draws(playerid, year, column, column_draws))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::projection::tests::{batting_row, load, parameters, pitching_row};
+
+    fn projections(name: &str) -> (Vec<BattingProjection>, Vec<PitchingProjection>) {
+        let mut batting = Vec::new();
+        let mut pitching = Vec::new();
+        for year in 2013..2016 {
+            batting.push(batting_row("bat01", year, "NYA", "AL", 500, 150, 25));
+            batting.push(batting_row("bat02", year, "NYA", "AL", 60, 12, 1));
+            pitching.push(pitching_row("sp01", year, 32, 32, 540));
+            pitching.push(pitching_row("rp01", year, 60, 0, 180));
+        }
+        let mut capuchin = load(name, parameters(), &batting, &pitching);
+        (capuchin.batting_projection(2016).0, capuchin.pitching_projection(2016).0)
+    }
+
+    fn to_json(percentiles: &[Percentiles]) -> Vec<serde_json::Value> {
+        percentiles.iter()
+            .map(|row| serde_json::to_value(row).expect("Unable to serialize percentiles."))
+            .collect()
+    }
+
+    // The single simulated season of each stat for each player, from a run of one simulation.
+    fn season(percentiles: &[Percentiles], playerid: &str) -> BTreeMap<String, f64> {
+        to_json(percentiles).iter()
+            .filter(|row| row["playerid"] == playerid)
+            .map(|row| (row["stat"].as_str().unwrap().to_string(), row["p50"].as_f64().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_simulation() {
+        let (batting, pitching) = projections("same-seed");
+        assert_eq!(to_json(&simulate_batting(&batting, 2016, 200, 7)),
+                   to_json(&simulate_batting(&batting, 2016, 200, 7)));
+        assert_eq!(to_json(&simulate_pitching(&pitching, 2016, 200, 7)),
+                   to_json(&simulate_pitching(&pitching, 2016, 200, 7)));
+        assert_ne!(to_json(&simulate_batting(&batting, 2016, 200, 7)),
+                   to_json(&simulate_batting(&batting, 2016, 200, 8)));
+    }
+
+    #[test]
+    fn simulated_seasons_fit_playing_time() {
+        let (batting, pitching) = projections("fit");
+        for seed in 0..200 {
+            let simulated = simulate_batting(&batting, 2016, 1, seed);
+            for playerid in &["bat01", "bat02"] {
+                let season = season(&simulated, playerid);
+                assert!(season.values().all(|value| *value >= 0.0), "{:?}", season);
+                let pa = season["pa"];
+                assert!(season["ab"] <= pa);
+                assert!(season["h"] <= season["ab"]);
+                assert!(season["2B"] + season["3B"] + season["hr"] <= season["h"]);
+                assert!(season["ab"] + season["bb"] + season["hbp"] <= pa);
+                for stat in &["r", "rbi", "sb", "cs", "so"] {
+                    assert!(season[*stat] <= pa, "{} {:?}", stat, season);
+                }
+            }
+
+            let simulated = simulate_pitching(&pitching, 2016, 1, seed);
+            for playerid in &["sp01", "rp01"] {
+                let season = season(&simulated, playerid);
+                assert!(season.values().all(|value| *value >= 0.0), "{:?}", season);
+                assert!(season["so"] <= (season["ip"] * 3.0).round());
+                assert!(season["hr"] <= season["h"]);
+                assert!(season["er"] <= season["r"]);
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::databank::{BattingStat, PitchingStat};
+use crate::linearweights::LinearWeights;
+use crate::metrics::ErrorMetrics;
+use crate::projection::{self, Capuchin, Parameters};
+
+
+// Multipliers of the starting regression amounts tried by the grid search.
+const GRID_REGRESS: &[f32] = &[0.5, 0.75, 1.0, 1.25, 1.5];
+// Offsets from the starting peak age tried by the grid search.
+const GRID_PEAK_AGE: &[i8] = &[-2, -1, 0, 1, 2];
+// Give up on coordinate descent after this many passes over every parameter.
+const MAX_PASSES: usize = 50;
+
+
+/// What the tuning minimizes.
+#[derive(Clone, Copy, Debug)]
+pub enum Objective {
+    /// RMSE of projected and actual wOBA, weighted by actual PA.
+    Woba,
+    /// RMSE of projected and actual ERA, weighted by actual IP.
+    Era,
+}
+
+/// A single parameter the optimizer can move.
+#[derive(Clone, Copy, Debug)]
+enum Dimension {
+    PeakAge,
+    BatterRegress,
+    StarterRegress,
+    RelieverRegress,
+    BatterWeight(usize),
+    PitcherWeight(usize),
+}
+
+/// The best parameters found and how well they scored.
+pub struct Tuning {
+    pub parameters: Parameters,
+    pub score: f64,
+    pub evaluations: usize,
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(objective: &str) -> Result<Self, Self::Err> {
+        match objective.to_lowercase().as_str() {
+            "woba" => Ok(Objective::Woba),
+            "era" => Ok(Objective::Era),
+            _ => Err(format!("Unknown objective '{}', expected woba or era", objective)),
+        }
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Objective::Woba => write!(f, "PA-weighted wOBA RMSE"),
+            Objective::Era => write!(f, "IP-weighted ERA RMSE"),
+        }
+    }
+}
+
+impl Objective {
+    /// The parameters this objective tunes, in the same form as the commandline options.
+    pub fn describe(&self, parameters: &Parameters) -> String {
+        match self {
+            Objective::Woba => {
+                format!("--peak-age {} --batter-regress {} --batter-weights {}",
+                        parameters.peak_age, parameters.batter_regress,
+                        join_weights(&parameters.batter_weights))
+            }
+            Objective::Era => {
+                format!("--peak-age {} --starter-regress {} --reliever-regress {} --pitcher-weights {}",
+                        parameters.peak_age, parameters.starter_regress,
+                        parameters.reliever_regress, join_weights(&parameters.pitcher_weights))
+            }
+        }
+    }
+
+    fn dimensions(&self, parameters: &Parameters) -> Vec<Dimension> {
+        let mut dimensions = vec![Dimension::PeakAge];
+        match self {
+            Objective::Woba => {
+                dimensions.push(Dimension::BatterRegress);
+                dimensions.extend((0..parameters.batter_weights.len()).map(Dimension::BatterWeight));
+            }
+            Objective::Era => {
+                dimensions.push(Dimension::StarterRegress);
+                dimensions.push(Dimension::RelieverRegress);
+                dimensions.extend((0..parameters.pitcher_weights.len()).map(Dimension::PitcherWeight));
+            }
+        }
+        dimensions
+    }
+
+    /// Every combination of regression amounts and peak ages around the starting parameters.
+    fn grid(&self, start: &Parameters) -> Vec<Parameters> {
+        let mut grid = Vec::new();
+        for age_offset in GRID_PEAK_AGE {
+            let mut parameters = start.clone();
+            parameters.peak_age = (start.peak_age as i16 + *age_offset as i16) as u8;
+            for regress in GRID_REGRESS {
+                match self {
+                    Objective::Woba => {
+                        let mut parameters = parameters.clone();
+                        parameters.batter_regress = scale(start.batter_regress, *regress, u16::MAX);
+                        grid.push(parameters);
+                    }
+                    Objective::Era => {
+                        for reliever_regress in GRID_REGRESS {
+                            let mut parameters = parameters.clone();
+                            parameters.starter_regress = scale(start.starter_regress, *regress,
+                                                               projection::MAX_PITCHER_REGRESS);
+                            parameters.reliever_regress = scale(start.reliever_regress,
+                                                                *reliever_regress,
+                                                                projection::MAX_PITCHER_REGRESS);
+                            grid.push(parameters);
+                        }
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    fn score(&self, capuchin: &mut Capuchin, start_year: u16, end_year: u16) -> f64 {
+        let mut errors = ErrorMetrics::new();
+        for year in start_year..=end_year {
+            match self {
+                Objective::Woba => {
+                    let (projections, context) = capuchin.batting_projection(year);
+                    // Score with the same weights the projections for the year use.
+                    let weights = match context.linear_weights {
+                        Some(weights) => weights,
+                        None => continue,
+                    };
+                    let actuals = capuchin.batting_actuals(year);
+                    for projection in &projections {
+                        if let Some(actual) = actuals.get(projection.playerid()) {
+                            let actual_woba = woba(&weights, |stat| actual.stat(stat));
+                            let projected_woba = woba(&weights, |stat| projection.stat(stat));
+                            if actual_woba.is_finite() && projected_woba.is_finite() {
+                                errors.add_weighted(projected_woba, actual_woba,
+                                                    actual.stat(BattingStat::Pa));
+                            }
+                        }
+                    }
+                }
+                Objective::Era => {
+                    let (projections, _) = capuchin.pitching_projection(year);
+                    let actuals = capuchin.pitching_actuals(year);
+                    for projection in &projections {
+                        if let Some(actual) = actuals.get(projection.playerid()) {
+                            let actual_ip = actual.stat(PitchingStat::Ip);
+                            if actual_ip > 0.0 && projection.era().is_finite() {
+                                let actual_era = actual.stat(PitchingStat::Er) * 9.0 / actual_ip;
+                                errors.add_weighted(projection.era(), actual_era, actual_ip);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        errors.rmse()
+    }
+}
+
+impl Dimension {
+    /// Starting step size for the coordinate descent and the smallest step worth trying.
+    fn steps(&self, parameters: &Parameters) -> (f32, f32) {
+        match self {
+            Dimension::PeakAge => (2.0, 1.0),
+            Dimension::BatterRegress => (parameters.batter_regress as f32 / 4.0, 10.0),
+            Dimension::StarterRegress => (parameters.starter_regress as f32 / 4.0, 1.0),
+            Dimension::RelieverRegress => (parameters.reliever_regress as f32 / 4.0, 1.0),
+            Dimension::BatterWeight(idx) => (parameters.batter_weights[*idx] / 4.0, 0.1),
+            Dimension::PitcherWeight(idx) => (parameters.pitcher_weights[*idx] / 4.0, 0.1),
+        }
+    }
+
+    /// Move the parameter by `step`, returning None when that leaves the sensible range.
+    fn shift(&self, parameters: &Parameters, step: f32) -> Option<Parameters> {
+        let mut parameters = parameters.clone();
+        match self {
+            Dimension::PeakAge => {
+                let age = (parameters.peak_age as f32 + step).round();
+                if !(20.0..=35.0).contains(&age) {
+                    return None;
+                }
+                parameters.peak_age = age as u8;
+            }
+            Dimension::BatterRegress => {
+                parameters.batter_regress = shift_regress(parameters.batter_regress, step, u16::MAX)?;
+            }
+            Dimension::StarterRegress => {
+                parameters.starter_regress = shift_regress(parameters.starter_regress, step,
+                                                        projection::MAX_PITCHER_REGRESS)?;
+            }
+            Dimension::RelieverRegress => {
+                parameters.reliever_regress = shift_regress(parameters.reliever_regress, step,
+                                                         projection::MAX_PITCHER_REGRESS)?;
+            }
+            Dimension::BatterWeight(idx) => {
+                parameters.batter_weights[*idx] = shift_weight(parameters.batter_weights[*idx], step)?;
+            }
+            Dimension::PitcherWeight(idx) => {
+                parameters.pitcher_weights[*idx] = shift_weight(parameters.pitcher_weights[*idx], step)?;
+            }
+        }
+        Some(parameters)
+    }
+}
+
+/// Search for the parameters that best project the years `start_year` to `end_year`. A coarse
+/// grid over the regression amounts and peak age picks a starting point, then coordinate descent
+/// refines every parameter, including the season weights, until no step improves the score.
+pub fn tune(capuchin: &mut Capuchin, objective: Objective, start_year: u16, end_year: u16) -> Tuning {
+    let start = capuchin.parameters().clone();
+    let mut evaluations = 0;
+    let mut evaluate = |capuchin: &mut Capuchin, parameters: &Parameters| {
+        evaluations += 1;
+        capuchin.set_parameters(parameters.clone());
+        let score = objective.score(capuchin, start_year, end_year);
+        if score.is_nan() { f64::INFINITY } else { score }
+    };
+
+    let mut best = start.clone();
+    let mut best_score = evaluate(capuchin, &start);
+    for parameters in objective.grid(&start) {
+        let score = evaluate(capuchin, &parameters);
+        if score < best_score {
+            best = parameters;
+            best_score = score;
+        }
+    }
+    eprintln!("Best grid point scored {:.6}: {}", best_score, objective.describe(&best));
+
+    let dimensions = objective.dimensions(&best);
+    let mut steps: Vec<(f32, f32)> = dimensions.iter()
+        .map(|dimension| dimension.steps(&best))
+        .collect();
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+        for (dimension, (step, min_step)) in dimensions.iter().zip(steps.iter_mut()) {
+            if *step < *min_step {
+                continue;
+            }
+            for direction in &[1.0, -1.0] {
+                let candidate = match dimension.shift(&best, *step * direction) {
+                    Some(candidate) => candidate,
+                    None => continue,
+                };
+                let score = evaluate(capuchin, &candidate);
+                if score < best_score {
+                    best = candidate;
+                    best_score = score;
+                    improved = true;
+                    eprintln!("Improved to {:.6}: {}", best_score, objective.describe(&best));
+                    break;
+                }
+            }
+        }
+
+        if !improved {
+            // Nothing helped at this resolution so look closer.
+            let mut refined = false;
+            for (step, min_step) in steps.iter_mut() {
+                *step /= 2.0;
+                refined |= *step >= *min_step;
+            }
+            if !refined {
+                break;
+            }
+        }
+    }
+
+    capuchin.set_parameters(best.clone());
+    Tuning {
+        parameters: best,
+        score: best_score,
+        evaluations,
+    }
+}
+
+/// wOBA using the linear weights of the scored year.
+fn woba<F: Fn(BattingStat) -> f32>(weights: &LinearWeights, stat: F) -> f32 {
+    let singles = stat(BattingStat::H) - stat(BattingStat::Double) - stat(BattingStat::Triple)
+        - stat(BattingStat::Hr);
+    let unintentional_bb = stat(BattingStat::Bb) - stat(BattingStat::Ibb);
+    let numerator = weights.bb * unintentional_bb + weights.hbp * stat(BattingStat::Hbp)
+        + weights.single * singles + weights.double * stat(BattingStat::Double)
+        + weights.triple * stat(BattingStat::Triple) + weights.hr * stat(BattingStat::Hr);
+    let denominator = stat(BattingStat::Ab) + unintentional_bb + stat(BattingStat::Sf)
+        + stat(BattingStat::Hbp);
+    numerator / denominator
+}
+
+fn scale(amount: u16, multiplier: f32, max: u16) -> u16 {
+    ((amount as f32 * multiplier).round().min(max as f32) as u16).max(1)
+}
+
+fn shift_regress(amount: u16, step: f32, max: u16) -> Option<u16> {
+    let amount = (amount as f32 + step).round();
+    if amount < 1.0 || amount > max as f32 {
+        None
+    }
+    else {
+        Some(amount as u16)
+    }
+}
+
+fn shift_weight(weight: f32, step: f32) -> Option<f32> {
+    let weight = weight + step;
+    if weight < 0.0 { None } else { Some(weight) }
+}
+
+// Print the weights the same way they are given on the commandline.
+fn join_weights(weights: &[f32]) -> String {
+    weights.iter()
+        .map(|weight| format!("{:.2}", weight))
+        .collect::<Vec<String>>()
+        .join(",")
+}
@@ -0,0 +1,218 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::databank::{BattingProjection, BattingStat, PitchingProjection, PitchingStat};
+
+
+// Standard normal quantile of the 90th percentile. The 10th is the same distance below.
+const Z_90: f32 = 1.281_552;
+
+const BATTING_STATS: &[BattingStat] = &[
+    BattingStat::R, BattingStat::H, BattingStat::Double, BattingStat::Hr, BattingStat::Rbi,
+    BattingStat::Sb, BattingStat::Bb, BattingStat::So,
+];
+const PITCHING_STATS: &[PitchingStat] = &[
+    PitchingStat::W, PitchingStat::Sv, PitchingStat::H, PitchingStat::Er, PitchingStat::Hr,
+    PitchingStat::So, PitchingStat::Bb,
+];
+
+
+/// Spread of one projected stat, with percentiles from a normal approximation.
+#[derive(Debug, Serialize)]
+pub struct Percentiles {
+    playerid: String,
+    year: u16,
+    stat: &'static str,
+    mean: f32,
+    sd: f32,
+    p10: f32,
+    p50: f32,
+    p90: f32,
+}
+
+impl Percentiles {
+    fn new(playerid: &str, year: u16, stat: &'static str, mean: f32, variance: f32) -> Self {
+        let sd = variance.max(0.0).sqrt();
+        Percentiles {
+            playerid: playerid.to_string(),
+            year,
+            stat,
+            mean,
+            sd,
+            p10: (mean - Z_90 * sd).max(0.0),
+            p50: mean,
+            p90: mean + Z_90 * sd,
+        }
+    }
+
+    /// Summarize draws of a stat, such as from simulated seasons, by their own percentiles.
+    pub fn from_draws(playerid: &str, year: u16, stat: &'static str, draws: &mut [f32]) -> Self {
+        let n = draws.len() as f32;
+        let mean = draws.iter().sum::<f32>() / n;
+        let variance = draws.iter().map(|draw| (draw - mean) * (draw - mean)).sum::<f32>() / n;
+        draws.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let percentile = |p: f32| draws[((n - 1.0) * p).round() as usize];
+        Percentiles {
+            playerid: playerid.to_string(),
+            year,
+            stat,
+            mean,
+            sd: variance.sqrt(),
+            p10: percentile(0.1),
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+        }
+    }
+}
+
+/// Percentiles of PA, AVG and the key counting stats for each batter.
+///
+/// Every event is a binomial draw per PA. Its variance has three parts: the season's own
+/// sampling, the uncertainty in the player's true rate and the PA themselves, which are treated as
+/// a Poisson draw. The rate uncertainty is the binomial variance over the sample behind the rate,
+/// the player's weighted PA divided by their reliability, so it shrinks as reliability grows.
+pub fn batting_percentiles(projections: &[BattingProjection], year: u16) -> Vec<Percentiles> {
+    let mut percentiles = Vec::new();
+    for projection in projections {
+        let playerid = projection.playerid();
+        let pa = projection.stat(BattingStat::Pa);
+        let sample = projection.sample();
+        percentiles.push(Percentiles::new(playerid, year, "pa", pa, pa));
+        for stat in BATTING_STATS {
+            let count = projection.stat(*stat);
+            let variance = count_variance(pa, count, sample, binomial);
+            percentiles.push(Percentiles::new(playerid, year, stat.name(), count, variance));
+        }
+
+        let ab = projection.stat(BattingStat::Ab);
+        if ab > 0.0 {
+            let avg = projection.stat(BattingStat::H) / ab;
+            let variance = rate_variance(ab, avg, sample, binomial);
+            percentiles.push(Percentiles::new(playerid, year, "avg", avg, variance));
+        }
+    }
+    percentiles
+}
+
+/// Percentiles of IP, ERA and the key counting stats for each pitcher.
+///
+/// Like batting except events are Poisson draws per out, since a pitcher can allow more than one
+/// hit or run for every out recorded. IP are a Poisson draw of outs.
+pub fn pitching_percentiles(projections: &[PitchingProjection], year: u16) -> Vec<Percentiles> {
+    let mut percentiles = Vec::new();
+    for projection in projections {
+        let playerid = projection.playerid();
+        let ip = projection.stat(PitchingStat::Ip);
+        let outs = ip * 3.0;
+        let sample = projection.sample();
+        percentiles.push(Percentiles::new(playerid, year, "ip", ip, outs / 9.0));
+        for stat in PITCHING_STATS {
+            let count = projection.stat(*stat);
+            let variance = count_variance(outs, count, sample, poisson);
+            percentiles.push(Percentiles::new(playerid, year, stat.name(), count, variance));
+        }
+
+        if outs > 0.0 {
+            let er_per_out = projection.stat(PitchingStat::Er) / outs;
+            let variance = 27.0 * 27.0 * rate_variance(outs, er_per_out, sample, poisson);
+            percentiles.push(Percentiles::new(playerid, year, "era", projection.era(), variance));
+        }
+    }
+    percentiles
+}
+
+pub fn write_batting_percentiles(percentiles: &[Percentiles], year: u16) -> Result<(), csv::Error> {
+    write_percentiles(percentiles, &format!("BattingPercentiles{}.csv", year))
+}
+
+pub fn write_pitching_percentiles(percentiles: &[Percentiles], year: u16) -> Result<(), csv::Error> {
+    write_percentiles(percentiles, &format!("PitchingPercentiles{}.csv", year))
+}
+
+pub fn write_percentiles(percentiles: &[Percentiles], output_file: &str) -> Result<(), csv::Error> {
+    let output_path = Path::new(output_file);
+    let mut wtr = csv::Writer::from_path(output_path)?;
+
+    for row in percentiles {
+        wtr.serialize(row)?;
+    }
+
+    Ok(())
+}
+
+fn binomial(rate: f32) -> f32 {
+    let rate = rate.min(1.0);
+    rate * (1.0 - rate)
+}
+
+fn poisson(rate: f32) -> f32 {
+    rate
+}
+
+// Variance of a count over `opportunities` PA or outs, including the variance of the
+// opportunities themselves.
+fn count_variance<F: Fn(f32) -> f32>(opportunities: f32, count: f32, sample: f32, draw: F) -> f32 {
+    if opportunities <= 0.0 {
+        return 0.0;
+    }
+    let rate = count / opportunities;
+    opportunities * opportunities * rate_variance(opportunities, rate, sample, draw)
+        + rate * rate * opportunities
+}
+
+// Variance of a per-opportunity rate from the season's own sampling and the uncertainty in the
+// player's true rate.
+fn rate_variance<F: Fn(f32) -> f32>(opportunities: f32, rate: f32, sample: f32, draw: F) -> f32 {
+    let per_event = draw(rate);
+    let sampling = per_event / opportunities;
+    let talent = if sample > 0.0 { per_event / sample } else { 0.0 };
+    sampling + talent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ordered(percentiles: &Percentiles) {
+        assert!(percentiles.p10 >= 0.0);
+        assert!(percentiles.p10 <= percentiles.p50 && percentiles.p50 <= percentiles.p90);
+    }
+
+    #[test]
+    fn percentiles_around_point_estimate() {
+        // 600 PA with 25 HR, against a sample of 1000 weighted PA.
+        let variance = count_variance(600.0, 25.0, 1000.0, binomial);
+        let percentiles = Percentiles::new("test01", 2016, "hr", 25.0, variance);
+        assert_eq!(percentiles.p50, 25.0);
+        assert_ordered(&percentiles);
+        assert!((percentiles.p90 - 25.0 - (25.0 - percentiles.p10)).abs() < 1e-4);
+
+        let variance = count_variance(540.0, 60.0, 900.0, poisson);
+        let percentiles = Percentiles::new("test01", 2016, "er", 60.0, variance);
+        assert_eq!(percentiles.p50, 60.0);
+        assert_ordered(&percentiles);
+    }
+
+    #[test]
+    fn percentiles_clamped_at_zero() {
+        // Two SB in 50 PA leaves more than a standard deviation below the mean.
+        let variance = count_variance(50.0, 2.0, 50.0, binomial);
+        let percentiles = Percentiles::new("test01", 2016, "sb", 2.0, variance);
+        assert_eq!(percentiles.p10, 0.0);
+        assert_eq!(percentiles.p50, 2.0);
+        assert_ordered(&percentiles);
+
+        let variance = count_variance(0.0, 0.0, 0.0, binomial);
+        let percentiles = Percentiles::new("test01", 2016, "sb", 0.0, variance);
+        assert_eq!((percentiles.p10, percentiles.p50, percentiles.p90), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn percentiles_from_draws() {
+        let mut draws: Vec<f32> = (0..=100).map(|draw| draw as f32).collect();
+        let percentiles = Percentiles::from_draws("test01", 2016, "hr", &mut draws);
+        assert_eq!(percentiles.mean, 50.0);
+        assert_eq!((percentiles.p10, percentiles.p50, percentiles.p90), (10.0, 50.0, 90.0));
+    }
+}